@@ -0,0 +1,156 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Proxy, Url,
+};
+
+use crate::error::{Error, Result};
+use crate::inner::API;
+use crate::retry::RetryPolicy;
+
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36 GLS/100.10.9939.100";
+
+/// IP family used for outgoing connections
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpVersion {
+    V4,
+    V6,
+}
+
+/// Configures the HTTP client behind [`API`]
+#[derive(Clone, Debug)]
+pub struct APIBuilder {
+    base_url: String,
+    user_agent: String,
+    proxy: Option<String>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    headers: Vec<(String, String)>,
+    local_address: Option<IpAddr>,
+    ip_version: Option<IpVersion>,
+    retry: RetryPolicy,
+}
+
+impl Default for APIBuilder {
+    fn default() -> Self {
+        APIBuilder {
+            base_url: "https://kemono.su".into(),
+            user_agent: USER_AGENT.into(),
+            proxy: None,
+            connect_timeout: None,
+            read_timeout: None,
+            timeout: None,
+            headers: Vec::new(),
+            local_address: None,
+            ip_version: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl APIBuilder {
+    /// Base url of the kemono-compatible site, e.g. `https://coomer.su`
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Route all requests through a proxy, e.g. `socks5://127.0.0.1:1080` or `http://proxy:8080`
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout between two successful reads of a response
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout of a whole request, including the body
+    ///
+    /// This also bounds file downloads, so leave it unset unless all files are small.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Header sent with every request, may be called multiple times
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Local address to bind outgoing connections to
+    pub fn local_address(mut self, addr: IpAddr) -> Self {
+        self.local_address = Some(addr);
+        self
+    }
+
+    /// Connect over the given IP family only, ignored if [`APIBuilder::local_address`] is set
+    pub fn ip_version(mut self, version: IpVersion) -> Self {
+        self.ip_version = Some(version);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<API> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|e| Error::Config(format!("header name {name:?}: {e}")))?;
+            let value = HeaderValue::try_from(value.as_str())
+                .map_err(|e| Error::Config(format!("value of header {name}: {e}")))?;
+            headers.append(name, value);
+        }
+
+        let mut client = Client::builder()
+            .user_agent(self.user_agent)
+            .default_headers(headers);
+        if let Some(proxy) = self.proxy {
+            client = client.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            client = client.read_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+        let local_address = self.local_address.or(match self.ip_version {
+            Some(IpVersion::V4) => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            Some(IpVersion::V6) => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+            None => None,
+        });
+        if local_address.is_some() {
+            client = client.local_address(local_address);
+        }
+
+        Ok(API::new(
+            client.build()?,
+            Url::parse(&self.base_url)?,
+            self.retry,
+        ))
+    }
+}
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use reqwest::{
+    header::{CONTENT_RANGE, RETRY_AFTER},
+    Method, Response, StatusCode,
+};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Maximum number of body bytes kept in [`Error::Decode`]
+const BODY_SNIPPET_LEN: usize = 256;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The requested creator, post or file does not exist (404 / 410)
+    #[error("{method} {url}: not found")]
+    NotFound { method: Method, url: String },
+
+    /// Server asked us to slow down (429)
+    #[error("{method} {url}: rate limited, retry after {retry_after:?}")]
+    RateLimited {
+        method: Method,
+        url: String,
+        retry_after: Option<Duration>,
+    },
+
+    /// Requested range starts beyond the end of the file (416)
+    #[error("{method} {url}: range not satisfiable, file size {total:?}")]
+    RangeNotSatisfiable {
+        method: Method,
+        url: String,
+        total: Option<u64>,
+    },
+
+    /// Server side failure (5xx)
+    #[error("{method} {url}: server error {status}")]
+    ServerError {
+        method: Method,
+        url: String,
+        status: StatusCode,
+    },
+
+    /// Any other non-successful status
+    #[error("{method} {url}: unexpected status {status}")]
+    Status {
+        method: Method,
+        url: String,
+        status: StatusCode,
+    },
+
+    /// Response body is not what the model expects
+    #[error("{method} {url}: failed to decode response: {source}, body: {body_snippet}")]
+    Decode {
+        method: Method,
+        url: String,
+        body_snippet: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// Connection, TLS, timeout or body streaming failure
+    #[error(transparent)]
+    Transport(#[from] reqwest::Error),
+
+    #[error("invalid url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    /// Client configuration rejected by [`crate::APIBuilder::build`]
+    #[error("invalid client config: {0}")]
+    Config(String),
+}
+
+impl Error {
+    /// Classify a non-successful response
+    pub(crate) fn from_response(method: &Method, url: &str, resp: &Response) -> Self {
+        let method = method.clone();
+        let url = url.to_string();
+        let status = resp.status();
+        match status {
+            StatusCode::NOT_FOUND | StatusCode::GONE => Error::NotFound { method, url },
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                method,
+                url,
+                retry_after: retry_after(resp),
+            },
+            StatusCode::RANGE_NOT_SATISFIABLE => Error::RangeNotSatisfiable {
+                method,
+                url,
+                total: unsatisfied_range_total(resp),
+            },
+            s if s.is_server_error() => Error::ServerError {
+                method,
+                url,
+                status,
+            },
+            _ => Error::Status {
+                method,
+                url,
+                status,
+            },
+        }
+    }
+
+    pub(crate) fn decode(
+        method: &Method,
+        url: &str,
+        body: &str,
+        source: serde_json::Error,
+    ) -> Self {
+        let mut end = body.len().min(BODY_SNIPPET_LEN);
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        Error::Decode {
+            method: method.clone(),
+            url: url.to_string(),
+            body_snippet: body[..end].to_string(),
+            source,
+        }
+    }
+
+    /// Status code of the failed response, if the server answered at all
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::NotFound { .. } => Some(StatusCode::NOT_FOUND),
+            Error::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            Error::RangeNotSatisfiable { .. } => Some(StatusCode::RANGE_NOT_SATISFIABLE),
+            Error::ServerError { status, .. } | Error::Status { status, .. } => Some(*status),
+            Error::Transport(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::RateLimited { .. } | Error::ServerError { .. } => true,
+            Error::Transport(e) => {
+                e.is_timeout() || e.is_connect() || e.is_request() || e.is_body()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse `Retry-After` in its delay-seconds form
+fn retry_after(resp: &Response) -> Option<Duration> {
+    resp.headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Parse the `bytes */<size>` form of `Content-Range` sent with a 416
+fn unsatisfied_range_total(resp: &Response) -> Option<u64> {
+    resp.headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.trim().strip_prefix("bytes */"))
+        .and_then(|s| s.parse().ok())
+}
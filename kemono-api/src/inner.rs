@@ -1,119 +1,311 @@
-use anyhow::Result;
-use reqwest::{Client, Url};
+use std::collections::VecDeque;
 
-use crate::model::{post_info::PostInfo, posts_legacy::PostsLegacy, user_profile::UserProfile};
+use futures_lite::{stream, Stream};
+use reqwest::{Client, Method, RequestBuilder, Response, Url};
+use serde::de::DeserializeOwned;
+use tokio::task::JoinHandle;
+
+use crate::builder::APIBuilder;
+use crate::error::{Error, Result};
+use crate::model::{
+    creator_tags::CreatorTag,
+    id::{CreatorId, PostId},
+    post_info::{Post, PostInfo},
+    posts_legacy::PostsLegacy,
+    service::Service,
+    user_profile::UserProfile,
+};
+use crate::retry::RetryPolicy;
 
 #[derive(Clone, Debug)]
 pub struct API {
     client: Client,
     base_url: Url,
+    retry: RetryPolicy,
 }
 
-const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36 GLS/100.10.9939.100";
-
 impl API {
+    /// Number of posts returned by one page of [`API::get_posts`]
+    pub const POSTS_PAGE_SIZE: usize = 50;
+
+    pub fn builder() -> APIBuilder {
+        APIBuilder::default()
+    }
+
     pub fn try_new() -> Result<Self> {
-        Ok(API {
-            client: Client::builder().user_agent(USER_AGENT).build()?,
-            base_url: Url::parse("https://kemono.su")?,
-        })
+        API::builder().build()
     }
 
     pub fn try_with_base_url(base_url: impl AsRef<str>) -> Result<Self> {
-        Ok(API {
-            client: Client::builder().user_agent(USER_AGENT).build()?,
-            base_url: Url::parse(base_url.as_ref())?,
+        API::builder().base_url(base_url.as_ref()).build()
+    }
+
+    pub(crate) fn new(client: Client, base_url: Url, retry: RetryPolicy) -> Self {
+        API {
+            client,
+            base_url,
+            retry,
+        }
+    }
+
+    /// Replace the retry policy applied to every request
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    pub async fn head(&self, url: &str) -> Result<Response> {
+        let base_url = &self.base_url;
+        self.send(url, || {
+            self.client
+                .head(url)
+                .header(reqwest::header::REFERER, base_url.as_str())
         })
+        .await
     }
 
-    pub async fn head(&self, url: &str) -> Result<reqwest::Response> {
+    pub async fn get_stream(&self, url: &str, start_pos: u64) -> Result<Response> {
         let base_url = &self.base_url;
-        let resp = self
-            .client
-            .head(url)
-            .header(reqwest::header::REFERER, base_url.as_str())
-            .send()
-            .await?;
-        Ok(resp)
+        self.send(url, || {
+            self.client
+                .get(url)
+                .header(reqwest::header::REFERER, base_url.as_str())
+                .header(reqwest::header::RANGE, format!("bytes={start_pos}-"))
+        })
+        .await
     }
 
-    pub async fn get_stream(&self, url: &str, start_pos: u64) -> Result<reqwest::Response> {
+    /// Fetch the inclusive byte range `start..=end` of a file
+    pub async fn get_range(&self, url: &str, start: u64, end: u64) -> Result<Response> {
         let base_url = &self.base_url;
-        let resp = self
-            .client
-            .get(url)
-            .header(reqwest::header::REFERER, base_url.as_str())
-            .header(reqwest::header::RANGE, format!("bytes={start_pos}-"))
-            .send()
-            .await?;
-        Ok(resp)
+        self.send(url, || {
+            self.client
+                .get(url)
+                .header(reqwest::header::REFERER, base_url.as_str())
+                .header(reqwest::header::RANGE, format!("bytes={start}-{end}"))
+        })
+        .await
     }
 
     pub async fn get_posts_legacy(
         &self,
-        web_name: &str,
-        user_id: &str,
+        service: &Service,
+        user_id: &CreatorId,
         offset: usize,
     ) -> Result<PostsLegacy> {
         let base_url = &self.base_url;
-        let url = format!("{base_url}/api/v1/{web_name}/user/{user_id}/posts-legacy",);
-        let mut req = self.client.get(&url).header(
-            reqwest::header::REFERER,
-            format!("{base_url}/{web_name}/user/{user_id}"),
-        );
-
-        if offset > 0 {
-            req = req.query(&[("o", offset)]);
-        }
+        let url = format!("{base_url}/api/v1/{service}/user/{user_id}/posts-legacy",);
+        self.get_json(&url, || {
+            let mut req = self.client.get(&url).header(
+                reqwest::header::REFERER,
+                format!("{base_url}/{service}/user/{user_id}"),
+            );
 
-        let resp = req.send().await?;
-        if !resp.status().is_success() {
-            let status = resp.status();
-            return Err(anyhow::anyhow!("GET {url} failed with status {status}",));
-        }
-        let val = resp.json().await?;
-        Ok(val)
+            if offset > 0 {
+                req = req.query(&[("o", offset)]);
+            }
+            req
+        })
+        .await
+    }
+
+    /// One page of a creator's posts, newest first, with files and attachments
+    ///
+    /// `offset` should be a multiple of [`API::POSTS_PAGE_SIZE`]
+    pub async fn get_posts(
+        &self,
+        service: &Service,
+        user_id: &CreatorId,
+        offset: usize,
+    ) -> Result<Vec<Post>> {
+        let base_url = &self.base_url;
+        let url = format!("{base_url}/api/v1/{service}/user/{user_id}/posts");
+        self.get_json(&url, || {
+            let mut req = self.client.get(&url).header(
+                reqwest::header::REFERER,
+                format!("{base_url}/{service}/user/{user_id}"),
+            );
+
+            if offset > 0 {
+                req = req.query(&[("o", offset)]);
+            }
+            req
+        })
+        .await
+    }
+
+    /// All posts of a creator, newest first
+    ///
+    /// Pages are fetched on demand, and the next page is requested in the background
+    /// while the current one is consumed. The stream ends after the last page or the first error.
+    /// Nothing is requested before the stream is first polled, so it can be created outside
+    /// a runtime, but must be polled inside one.
+    pub fn posts(
+        &self,
+        service: &Service,
+        user_id: &CreatorId,
+    ) -> impl Stream<Item = Result<Post>> + Send + 'static {
+        let pages = Pages {
+            api: self.clone(),
+            service: service.clone(),
+            user_id: user_id.clone(),
+            offset: 0,
+            page: VecDeque::new(),
+            next: None,
+            started: false,
+        };
+
+        stream::unfold(pages, |mut pages| async move {
+            // the first page is only requested once the stream is polled
+            if !pages.started {
+                pages.started = true;
+                pages.prefetch();
+            }
+            loop {
+                if let Some(post) = pages.page.pop_front() {
+                    return Some((Ok(post), pages));
+                }
+                let page = pages
+                    .next
+                    .take()?
+                    .await
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+                match page {
+                    Ok(page) => {
+                        let len = page.len();
+                        pages.offset += len;
+                        pages.page = page.into();
+                        if len >= Self::POSTS_PAGE_SIZE {
+                            pages.prefetch();
+                        }
+                    }
+                    Err(e) => return Some((Err(e), pages)),
+                }
+            }
+        })
     }
 
     pub async fn get_post_info(
         &self,
-        web_name: &str,
-        user_id: &str,
-        post_id: &str,
+        service: &Service,
+        user_id: &CreatorId,
+        post_id: &PostId,
     ) -> Result<PostInfo> {
         let base_url = &self.base_url;
-        let url = format!("{base_url}/api/v1/{web_name}/user/{user_id}/post/{post_id}");
-        let resp = self
-            .client
-            .get(&url)
-            .header(
+        let url = format!("{base_url}/api/v1/{service}/user/{user_id}/post/{post_id}");
+        self.get_json(&url, || {
+            self.client.get(&url).header(
                 reqwest::header::REFERER,
-                format!("{base_url}/{web_name}/user/{user_id}/post/{post_id}"),
+                format!("{base_url}/{service}/user/{user_id}/post/{post_id}"),
             )
-            .send()
-            .await?;
-        if !resp.status().is_success() {
-            let status = resp.status();
-            return Err(anyhow::anyhow!("GET {url} failed with status {status}",));
-        }
-        let val = resp.json().await?;
-        Ok(val)
+        })
+        .await
     }
 
-    pub async fn get_user_profile(&self, web_name: &str, user_id: &str) -> Result<UserProfile> {
+    pub async fn get_user_profile(
+        &self,
+        service: &Service,
+        user_id: &CreatorId,
+    ) -> Result<UserProfile> {
         let base_url = &self.base_url;
-        let url = format!("{base_url}/api/v1/{web_name}/user/{user_id}/profile",);
-        let req = self.client.get(&url).header(
-            reqwest::header::REFERER,
-            format!("{base_url}/{web_name}/user/{user_id}"),
-        );
-
-        let resp = req.send().await?;
-        if !resp.status().is_success() {
-            let status = resp.status();
-            return Err(anyhow::anyhow!("GET {url} failed with status {status}",));
+        let url = format!("{base_url}/api/v1/{service}/user/{user_id}/profile",);
+        self.get_json(&url, || {
+            self.client.get(&url).header(
+                reqwest::header::REFERER,
+                format!("{base_url}/{service}/user/{user_id}"),
+            )
+        })
+        .await
+    }
+
+    /// Tags used by a creator's posts, with the number of posts having each
+    pub async fn get_creator_tags(
+        &self,
+        service: &Service,
+        user_id: &CreatorId,
+    ) -> Result<Vec<CreatorTag>> {
+        let base_url = &self.base_url;
+        let url = format!("{base_url}/api/v1/{service}/user/{user_id}/tags");
+        self.get_json(&url, || {
+            self.client.get(&url).header(
+                reqwest::header::REFERER,
+                format!("{base_url}/{service}/user/{user_id}/tags"),
+            )
+        })
+        .await
+    }
+
+    /// Send the request built by `req`, retrying on transient failures
+    async fn send(&self, url: &str, req: impl Fn() -> RequestBuilder) -> Result<Response> {
+        self.retry
+            .run(|| async {
+                let request = req().build()?;
+                let method = request.method().clone();
+                check_status(&method, url, self.client.execute(request).await?)
+            })
+            .await
+    }
+
+    /// Like [`API::send`], but also retries if the body is cut off while decoding
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        req: impl Fn() -> RequestBuilder,
+    ) -> Result<T> {
+        self.retry
+            .run(|| async {
+                let request = req().build()?;
+                let method = request.method().clone();
+                json(&method, url, self.client.execute(request).await?).await
+            })
+            .await
+    }
+}
+
+fn check_status(method: &Method, url: &str, resp: Response) -> Result<Response> {
+    if !resp.status().is_success() {
+        return Err(Error::from_response(method, url, &resp));
+    }
+    Ok(resp)
+}
+
+async fn json<T: DeserializeOwned>(method: &Method, url: &str, resp: Response) -> Result<T> {
+    let resp = check_status(method, url, resp)?;
+    let body = resp.text().await?;
+    serde_json::from_str(&body).map_err(|e| Error::decode(method, url, &body, e))
+}
+
+/// Pagination state of [`API::posts`]
+struct Pages {
+    api: API,
+    service: Service,
+    user_id: CreatorId,
+    offset: usize,
+    page: VecDeque<Post>,
+    next: Option<JoinHandle<Result<Vec<Post>>>>,
+    started: bool,
+}
+
+impl Pages {
+    fn prefetch(&mut self) {
+        let api = self.api.clone();
+        let service = self.service.clone();
+        let user_id = self.user_id.clone();
+        let offset = self.offset;
+        self.next = Some(tokio::spawn(async move {
+            api.get_posts(&service, &user_id, offset).await
+        }));
+    }
+}
+
+impl Drop for Pages {
+    fn drop(&mut self) {
+        if let Some(next) = &self.next {
+            next.abort();
         }
-        let val = resp.json().await?;
-        Ok(val)
     }
 }
@@ -1,8 +1,22 @@
+mod builder;
+mod error;
 mod inner;
+mod range;
+mod retry;
 
 pub mod model;
 
+pub use builder::{APIBuilder, IpVersion};
+pub use error::{Error, Result};
 pub use inner::API;
+pub use model::{
+    id::{CreatorId, PostId},
+    service::Service,
+    timestamp::Timestamp,
+};
+pub use range::ContentRange;
+pub use retry::{CancelFlag, RetryPolicy};
 
+pub use chrono;
 pub use reqwest;
 pub use serde_json;
@@ -0,0 +1,10 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A tag used by a creator's posts
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CreatorTag {
+    pub tag: String,
+    pub post_count: u64,
+}
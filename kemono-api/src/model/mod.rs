@@ -1,3 +1,7 @@
+pub mod creator_tags;
+pub mod id;
 pub mod post_info;
 pub mod posts_legacy;
+pub mod service;
+pub mod timestamp;
 pub mod user_profile;
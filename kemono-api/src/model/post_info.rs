@@ -1,6 +1,10 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::id::{CreatorId, PostId};
+use super::service::Service;
+use super::timestamp::Timestamp;
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct PostInfo {
@@ -17,17 +21,22 @@ pub struct AttachmentLike {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Post {
-    pub id: String,
-    pub user: String,
-    pub service: String,
+    pub id: PostId,
+    pub user: CreatorId,
+    pub service: Service,
     pub title: String,
     pub content: String,
+    /// Start of the content, sent by the creator listing in place of `content`, `embed`,
+    /// `poll` and `tags`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub substring: Option<String>,
     pub embed: Embed,
     pub shared_file: bool,
-    pub added: String,
-    pub published: String,
-    pub edited: Option<String>,
+    pub added: Option<Timestamp>,
+    pub published: Option<Timestamp>,
+    pub edited: Option<Timestamp>,
     pub file: File,
     pub attachments: Vec<AttachmentLike>,
     pub poll: Option<Poll>,
@@ -54,8 +63,8 @@ pub struct Embed {
 pub struct Poll {
     pub title: String,
     pub choices: Vec<Choice>,
-    pub closes_at: Option<String>,
-    pub created_at: String,
+    pub closes_at: Option<Timestamp>,
+    pub created_at: Option<Timestamp>,
     pub description: Option<String>,
     pub allows_multiple: bool,
     pub total_votes: i64,
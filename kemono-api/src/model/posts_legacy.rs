@@ -1,6 +1,8 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::id::PostId;
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct PostsLegacy {
@@ -18,6 +20,6 @@ pub struct Props {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Result {
-    pub id: String,
+    pub id: PostId,
     pub title: String,
 }
@@ -0,0 +1,95 @@
+use std::{convert::Infallible, fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Platform a creator publishes on, as it appears in kemono URLs
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Service {
+    Patreon,
+    Fanbox,
+    Fantia,
+    Gumroad,
+    SubscribeStar,
+    Dlsite,
+    Discord,
+    Boosty,
+    Afdian,
+    OnlyFans,
+    Fansly,
+    CandFans,
+    /// A service this crate does not know about yet
+    Other(String),
+}
+
+impl Service {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Service::Patreon => "patreon",
+            Service::Fanbox => "fanbox",
+            Service::Fantia => "fantia",
+            Service::Gumroad => "gumroad",
+            Service::SubscribeStar => "subscribestar",
+            Service::Dlsite => "dlsite",
+            Service::Discord => "discord",
+            Service::Boosty => "boosty",
+            Service::Afdian => "afdian",
+            Service::OnlyFans => "onlyfans",
+            Service::Fansly => "fansly",
+            Service::CandFans => "candfans",
+            Service::Other(s) => s,
+        }
+    }
+}
+
+impl Default for Service {
+    fn default() -> Self {
+        Service::Other(String::new())
+    }
+}
+
+impl From<&str> for Service {
+    fn from(s: &str) -> Self {
+        match s {
+            "patreon" => Service::Patreon,
+            "fanbox" => Service::Fanbox,
+            "fantia" => Service::Fantia,
+            "gumroad" => Service::Gumroad,
+            "subscribestar" => Service::SubscribeStar,
+            "dlsite" => Service::Dlsite,
+            "discord" => Service::Discord,
+            "boosty" => Service::Boosty,
+            "afdian" => Service::Afdian,
+            "onlyfans" => Service::OnlyFans,
+            "fansly" => Service::Fansly,
+            "candfans" => Service::CandFans,
+            other => Service::Other(other.to_string()),
+        }
+    }
+}
+
+impl FromStr for Service {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.into())
+    }
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Service {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Service {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.as_str().into())
+    }
+}
@@ -0,0 +1,144 @@
+use std::{cmp::Ordering, fmt};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Formats without timezone, which kemono always sends in UTC
+const NAIVE_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+/// A point in time as sent by the API
+///
+/// The original string is kept and serialized back unchanged,
+/// so `metadata.json` round-trips even for formats that fail to parse.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Timestamp {
+    raw: String,
+    datetime: Option<DateTime<Utc>>,
+}
+
+impl Timestamp {
+    pub fn parse(raw: impl Into<String>) -> Self {
+        let raw = raw.into();
+        let datetime = parse_datetime(raw.trim());
+        Timestamp { raw, datetime }
+    }
+
+    /// The string exactly as the server sent it
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Parsed value, `None` if the server sent a format we do not understand
+    pub fn datetime(&self) -> Option<DateTime<Utc>> {
+        self.datetime
+    }
+}
+
+fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.to_utc());
+    }
+    for format in NAIVE_FORMATS {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(dt.and_utc());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Some(date.and_time(Default::default()).and_utc());
+    }
+    DateTime::parse_from_rfc2822(s).ok().map(|dt| dt.to_utc())
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        Timestamp {
+            raw: datetime
+                .naive_utc()
+                .format("%Y-%m-%dT%H:%M:%S%.f")
+                .to_string(),
+            datetime: Some(datetime),
+        }
+    }
+}
+
+/// Ordered by time, unparsed timestamps first
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.datetime
+            .cmp(&other.datetime)
+            .then_with(|| self.raw.cmp(&other.raw))
+    }
+}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Timestamp::parse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()
+    }
+
+    /// Deserialize and serialize `json` back, the result must be unchanged
+    fn round_trip(json: &str) -> Option<Timestamp> {
+        let ts: Option<Timestamp> = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_string(&ts).unwrap(), json);
+        ts
+    }
+
+    #[test]
+    fn parses_naive_utc_times() {
+        let ts = round_trip(r#""2024-01-31T15:30:00""#).unwrap();
+        assert_eq!(ts.datetime(), Some(utc(2024, 1, 31, 15, 30, 0)));
+    }
+
+    #[test]
+    fn parses_fractional_seconds() {
+        let ts = round_trip(r#""2024-01-31T15:30:00.123456""#).unwrap();
+        let expected = utc(2024, 1, 31, 15, 30, 0) + chrono::TimeDelta::microseconds(123456);
+        assert_eq!(ts.datetime(), Some(expected));
+    }
+
+    #[test]
+    fn parses_rfc3339() {
+        let ts = round_trip(r#""2024-01-31T17:30:00+02:00""#).unwrap();
+        assert_eq!(ts.datetime(), Some(utc(2024, 1, 31, 15, 30, 0)));
+    }
+
+    #[test]
+    fn keeps_unparseable_strings_verbatim() {
+        let ts = round_trip(r#""sometime in 2024""#).unwrap();
+        assert_eq!(ts.datetime(), None);
+        assert_eq!(ts.as_str(), "sometime in 2024");
+    }
+
+    #[test]
+    fn null_is_none() {
+        assert_eq!(round_trip("null"), None);
+    }
+}
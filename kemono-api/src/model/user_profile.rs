@@ -1,10 +1,13 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::id::CreatorId;
+use super::service::Service;
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserProfile {
-    pub id: String,
+    pub id: CreatorId,
     pub name: String,
-    pub service: String,
+    pub service: Service,
     pub public_id: Option<String>,
 }
@@ -0,0 +1,33 @@
+use reqwest::{header::CONTENT_RANGE, Response};
+
+/// Parsed `Content-Range: bytes <start>-<end>/<total>` of a 206 response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    pub start: u64,
+    /// Inclusive
+    pub end: u64,
+    /// `None` if the server sent `*`
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (range, total) = s.trim().strip_prefix("bytes ")?.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        Some(ContentRange {
+            start: start.trim().parse().ok()?,
+            end: end.trim().parse().ok()?,
+            total: match total.trim() {
+                "*" => None,
+                total => Some(total.parse().ok()?),
+            },
+        })
+    }
+
+    pub fn from_response(resp: &Response) -> Option<Self> {
+        resp.headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(ContentRange::parse)
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::error::{Error, Result};
+
+/// How requests are retried when they fail with a transient error
+///
+/// Delays grow exponentially from `base_delay` and are capped at `max_delay`.
+/// A `Retry-After` header sent with 429 takes precedence over the computed delay,
+/// within the same cap. Waiting stops early once the `cancel` flag is set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Fraction of the delay that is randomized, in `0.0..=1.0`
+    pub jitter: f64,
+    pub respect_retry_after: bool,
+    pub cancel: Option<CancelFlag>,
+}
+
+/// Flag that interrupts retry delays once set, e.g. from a Ctrl-C handler
+#[derive(Clone, Copy)]
+pub struct CancelFlag(&'static AtomicBool);
+
+impl CancelFlag {
+    pub fn new(flag: &'static AtomicBool) -> Self {
+        CancelFlag(flag)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for CancelFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CancelFlag").field(&self.is_set()).finish()
+    }
+}
+
+impl PartialEq for CancelFlag {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+/// How often a cancellable delay checks its flag
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: 0.5,
+            respect_retry_after: true,
+            cancel: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a request that failed on `attempt` (starting from 1) should be sent again
+    pub fn should_retry(&self, attempt: u32, err: &Error) -> bool {
+        attempt < self.max_attempts && err.is_transient()
+    }
+
+    /// Delay before the attempt following `attempt`
+    pub fn delay(&self, attempt: u32, err: &Error) -> Duration {
+        if self.respect_retry_after {
+            if let Error::RateLimited {
+                retry_after: Some(retry_after),
+                ..
+            } = err
+            {
+                return (*retry_after).min(self.max_delay);
+            }
+        }
+
+        self.backoff(attempt)
+    }
+
+    /// Exponential delay with jitter, ignoring any server hint
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(16);
+        let delay = self.base_delay.saturating_mul(1 << exp).min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        delay.mul_f64(1.0 - jitter * fastrand::f64())
+    }
+
+    /// Wait for `delay`, returns `false` if cancelled before it elapsed
+    pub async fn sleep(&self, delay: Duration) -> bool {
+        let Some(cancel) = self.cancel else {
+            tokio::time::sleep(delay).await;
+            return true;
+        };
+        let deadline = Instant::now() + delay;
+        loop {
+            if cancel.is_set() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            tokio::time::sleep((deadline - now).min(CANCEL_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Run `f` until it succeeds, fails with a permanent error or runs out of attempts
+    pub async fn run<T, F, Fut>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match f().await {
+                Err(e) if self.should_retry(attempt, &e) => {
+                    if !self.sleep(self.delay(attempt, &e)).await {
+                        return Err(e);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Media categories for `--only`, told apart by file extension or MIME type
+
+/// Kind of a downloaded file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Category {
+    Images,
+    Videos,
+    Audio,
+    Archives,
+    Documents,
+}
+
+const EXTENSIONS: &[(Category, &[&str])] = &[
+    (
+        Category::Images,
+        &[
+            "jpg", "jpeg", "jfif", "png", "gif", "webp", "avif", "bmp", "tif", "tiff", "heic",
+            "heif", "jxl", "svg", "psd",
+        ],
+    ),
+    (
+        Category::Videos,
+        &[
+            "mp4", "m4v", "mov", "webm", "mkv", "avi", "wmv", "flv", "mpg", "mpeg", "m2ts",
+        ],
+    ),
+    (
+        Category::Audio,
+        &[
+            "mp3", "m4a", "aac", "flac", "wav", "ogg", "oga", "opus", "wma", "aif", "aiff",
+        ],
+    ),
+    (
+        Category::Archives,
+        &[
+            "zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "xz", "zst", "lzh",
+        ],
+    ),
+    (
+        Category::Documents,
+        &[
+            "pdf", "txt", "md", "rtf", "doc", "docx", "odt", "epub", "xls", "xlsx", "ppt", "pptx",
+        ],
+    ),
+];
+
+/// MIME types not covered by their `image/`, `video/` or `audio/` prefix
+const MIME_TYPES: &[(Category, &[&str])] = &[
+    (
+        Category::Archives,
+        &[
+            "application/zip",
+            "application/x-zip-compressed",
+            "application/vnd.rar",
+            "application/x-rar-compressed",
+            "application/x-7z-compressed",
+            "application/x-tar",
+            "application/gzip",
+            "application/x-bzip2",
+            "application/x-xz",
+            "application/zstd",
+        ],
+    ),
+    (
+        Category::Documents,
+        &[
+            "application/pdf",
+            "text/plain",
+            "text/markdown",
+            "application/rtf",
+            "application/msword",
+            "application/epub+zip",
+            "application/vnd.ms-excel",
+            "application/vnd.ms-powerpoint",
+            "application/vnd.oasis.opendocument.text",
+        ],
+    ),
+];
+
+impl Category {
+    /// `None` for extensions not in the table
+    pub fn from_ext(ext: &str) -> Option<Self> {
+        let ext = ext.to_ascii_lowercase();
+        EXTENSIONS
+            .iter()
+            .find(|(_, exts)| exts.contains(&ext.as_str()))
+            .map(|(category, _)| *category)
+    }
+
+    /// Category of a `Content-Type` value, parameters like `; charset=utf-8` are ignored
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.split(';').next().unwrap_or_default().trim();
+        let mime = mime.to_ascii_lowercase();
+        match mime.split_once('/') {
+            Some(("image", _)) => return Some(Category::Images),
+            Some(("video", _)) => return Some(Category::Videos),
+            Some(("audio", _)) => return Some(Category::Audio),
+            _ => {}
+        }
+        if mime.starts_with("application/vnd.openxmlformats-officedocument.") {
+            return Some(Category::Documents);
+        }
+        MIME_TYPES
+            .iter()
+            .find(|(_, types)| types.contains(&mime.as_str()))
+            .map(|(category, _)| *category)
+    }
+}
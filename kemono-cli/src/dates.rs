@@ -0,0 +1,81 @@
+//! Post selection by date, `--since` and `--until`
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Months, TimeDelta, Utc};
+use kemono_api::{model::post_info::Post, Timestamp};
+
+/// Which post date `--since` and `--until` are compared with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DateField {
+    #[default]
+    Published,
+    /// When the post was imported
+    Added,
+}
+
+impl DateField {
+    fn get(self, post: &Post) -> Option<DateTime<Utc>> {
+        match self {
+            DateField::Published => post.published.as_ref(),
+            DateField::Added => post.added.as_ref(),
+        }
+        .and_then(Timestamp::datetime)
+    }
+}
+
+/// Parse a date like `2024-01-31` or a time before now like `30d`
+///
+/// Units of relative dates: `h` hours, `d` days, `w` weeks, `m` months and `y` years.
+pub fn parse_date(s: &str) -> Result<DateTime<Utc>> {
+    let relative = s
+        .char_indices()
+        .last()
+        .filter(|(_, unit)| "hdwmy".contains(*unit))
+        .and_then(|(i, unit)| Some((s[..i].parse::<u32>().ok()?, unit)));
+    if let Some((n, unit)) = relative {
+        let now = Utc::now();
+        let date = match unit {
+            'h' => TimeDelta::try_hours(n.into()).and_then(|d| now.checked_sub_signed(d)),
+            'd' => TimeDelta::try_days(n.into()).and_then(|d| now.checked_sub_signed(d)),
+            'w' => TimeDelta::try_weeks(n.into()).and_then(|d| now.checked_sub_signed(d)),
+            'm' => now.checked_sub_months(Months::new(n)),
+            _ => now.checked_sub_months(Months::new(n.saturating_mul(12))),
+        };
+        return date.ok_or_else(|| anyhow!("{s} is too far back"));
+    }
+    Timestamp::parse(s)
+        .datetime()
+        .ok_or_else(|| anyhow!("invalid date {s}, expected e.g. 2024-01-31 or 30d"))
+}
+
+/// Posts dated from `since` up to, but not including, `until`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub field: DateField,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    /// Posts without the date are only selected if the range is unbounded
+    pub fn contains(&self, post: &Post) -> bool {
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+        self.field.get(post).is_some_and(|date| {
+            self.since.is_none_or(|since| date >= since)
+                && self.until.is_none_or(|until| date < until)
+        })
+    }
+
+    /// Whether `post` was published before the range, so no later post of the listing is in it
+    ///
+    /// Creator listings are ordered by publish date, newest first, so this is always
+    /// `false` when selecting by import date.
+    pub fn is_past(&self, post: &Post) -> bool {
+        match (self.field, self.since, self.field.get(post)) {
+            (DateField::Published, Some(since), Some(date)) => date < since,
+            _ => false,
+        }
+    }
+}
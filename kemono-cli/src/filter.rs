@@ -0,0 +1,889 @@
+//! `--filter` expressions over post and file attributes
+//!
+//! ```text
+//! title ~ "(?i)melody" and (ext in [mp4, m4v] or size > 100MB) and not tag == "wip"
+//! ```
+//!
+//! Fields: `title`, `content`, `tag`, `service`, `id`, `published`, `added`, `edited`,
+//! `name`, `ext`, `size` and `type` (a media category like `videos`, from the extension
+//! or else the MIME type). Operators: `==`, `!=`, `~` and `!~` (regex) and `in [a, b]`,
+//! all case-insensitive, and `<`, `<=`, `>`, `>=` on dates and sizes.
+//! A `tag` condition holds if any tag matches, `!=` and `!~` if none does. Dates are
+//! days like `2024-01-31`, compared by UTC day, or times relative to now like `30d`.
+//!
+//! Post, file name and file size become known at different stages of a download, so an
+//! expression is evaluated with three-valued logic: comparisons on missing attributes stay
+//! undecided and [`Filter::reduce`] returns what is left to check once they are known.
+
+use std::{cmp::Ordering, fmt};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use kemono_api::{model::post_info::Post, Timestamp};
+use regex::{Regex, RegexBuilder};
+
+use crate::{category::Category, dates::parse_date, utils::parse_size};
+
+/// A compiled filter expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Const(bool),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Cmp(Cmp),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cmp {
+    field: Field,
+    op: Op,
+    value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Content,
+    Tag,
+    Service,
+    Id,
+    Published,
+    Added,
+    Edited,
+    Name,
+    Ext,
+    Size,
+    Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Match,
+    NotMatch,
+    In,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    /// Lowercase, for case-insensitive comparison
+    Str(String),
+    List(Vec<String>),
+    Regex(Regex),
+    Size(u64),
+    Date(DateTime<Utc>),
+    /// A date without time, matching the whole UTC day
+    Day(NaiveDate),
+    Categories(Vec<Category>),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Regex(a), Value::Regex(b)) => a.as_str() == b.as_str(),
+            (Value::Size(a), Value::Size(b)) => a == b,
+            (Value::Date(a), Value::Date(b)) => a == b,
+            (Value::Day(a), Value::Day(b)) => a == b,
+            (Value::Categories(a), Value::Categories(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+/// Attributes known at the current stage, `None` for those not known yet
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Attrs<'a> {
+    pub post: Option<&'a Post>,
+    pub file_name: Option<&'a str>,
+    pub size: Option<u64>,
+    /// `Content-Type` from the HEAD request, empty if the server sent none
+    pub mime: Option<&'a str>,
+    /// `post` is a creator listing entry, which has no content and may have no tags
+    pub listing: bool,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "title" => Field::Title,
+            "content" => Field::Content,
+            "tag" | "tags" => Field::Tag,
+            "service" => Field::Service,
+            "id" => Field::Id,
+            "published" => Field::Published,
+            "added" => Field::Added,
+            "edited" => Field::Edited,
+            "name" => Field::Name,
+            "ext" => Field::Ext,
+            "size" => Field::Size,
+            "type" => Field::Type,
+            _ => return None,
+        })
+    }
+
+    fn is_post(self) -> bool {
+        !matches!(self, Field::Name | Field::Ext | Field::Size | Field::Type)
+    }
+
+    fn is_date(self) -> bool {
+        matches!(self, Field::Published | Field::Added | Field::Edited)
+    }
+
+    fn is_text(self) -> bool {
+        !self.is_date() && !matches!(self, Field::Size | Field::Type)
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+            Op::In => "in",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        })
+    }
+}
+
+impl Op {
+    fn test(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+            Op::Match | Op::NotMatch | Op::In => false,
+        }
+    }
+}
+
+fn extension(file_name: &str) -> &str {
+    match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => ext,
+        _ => "",
+    }
+}
+
+impl Cmp {
+    fn new(field: Field, op: Op, value: Token) -> Result<Self> {
+        let text = match &value {
+            Token::Str(s) | Token::Word(s) => Some(s.as_str()),
+            _ => None,
+        };
+        let value = match (field, op, &value) {
+            (Field::Type, Op::Eq | Op::Ne | Op::In, _) => {
+                let names = match &value {
+                    Token::List(items) => items.clone(),
+                    _ => vec![text
+                        .ok_or_else(|| anyhow!("type expects a category"))?
+                        .into()],
+                };
+                let categories = names
+                    .iter()
+                    .map(|name| {
+                        <Category as clap::ValueEnum>::from_str(name, true)
+                            .map_err(|_| anyhow!("unknown file type {name}"))
+                    })
+                    .collect::<Result<_>>()?;
+                Value::Categories(categories)
+            }
+            (_, Op::In, Token::List(items)) if field.is_text() => {
+                Value::List(items.iter().map(|item| item.to_lowercase()).collect())
+            }
+            (_, Op::Match | Op::NotMatch, _) if field.is_text() => {
+                let pattern = text.ok_or_else(|| anyhow!("{op} expects a regex"))?;
+                let regex = RegexBuilder::new(pattern).case_insensitive(true).build()?;
+                Value::Regex(regex)
+            }
+            (Field::Size, Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge, _) => {
+                let size = text.ok_or_else(|| anyhow!("size expects a value like 100MB"))?;
+                Value::Size(parse_size(size)?)
+            }
+            (_, Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge, _) if field.is_date() => {
+                let date = text.ok_or_else(|| anyhow!("expected a date like 2024-01-31"))?;
+                match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                    Ok(day) => Value::Day(day),
+                    Err(_) => Value::Date(parse_date(date)?),
+                }
+            }
+            (_, Op::Eq | Op::Ne, _) if field.is_text() => {
+                let s = text.ok_or_else(|| anyhow!("{op} expects a string"))?;
+                Value::Str(s.to_lowercase())
+            }
+            (_, op, _) => {
+                let field = format!("{field:?}").to_lowercase();
+                anyhow::bail!("operator {op} is not supported for {field}")
+            }
+        };
+        Ok(Cmp { field, op, value })
+    }
+
+    fn test_str(&self, s: &str) -> bool {
+        match (&self.value, self.op) {
+            (Value::Str(v), Op::Eq) => s.to_lowercase() == *v,
+            (Value::Str(v), Op::Ne) => s.to_lowercase() != *v,
+            (Value::List(items), _) => items.contains(&s.to_lowercase()),
+            (Value::Regex(re), Op::Match) => re.is_match(s),
+            (Value::Regex(re), Op::NotMatch) => !re.is_match(s),
+            _ => false,
+        }
+    }
+
+    fn test_date(&self, timestamp: Option<&Timestamp>) -> bool {
+        match (&self.value, timestamp.and_then(Timestamp::datetime)) {
+            (Value::Date(v), Some(date)) => self.op.test(date.cmp(v)),
+            (Value::Day(v), Some(date)) => self.op.test(date.date_naive().cmp(v)),
+            _ => false,
+        }
+    }
+
+    /// `None` if the attribute is not known yet
+    fn eval(&self, attrs: &Attrs<'_>) -> Option<bool> {
+        Some(match self.field {
+            Field::Title => self.test_str(&attrs.post?.title),
+            Field::Content if attrs.listing => return None,
+            Field::Content => self.test_str(&attrs.post?.content),
+            Field::Service => self.test_str(attrs.post?.service.as_str()),
+            Field::Id => self.test_str(attrs.post?.id.as_str()),
+            Field::Tag => {
+                let tags = match attrs.post?.tags.as_deref() {
+                    Some(tags) => tags,
+                    None if attrs.listing => return None,
+                    None => &[],
+                };
+                match self.op {
+                    Op::Ne | Op::NotMatch => tags.iter().all(|tag| self.test_str(tag)),
+                    _ => tags.iter().any(|tag| self.test_str(tag)),
+                }
+            }
+            Field::Published => self.test_date(attrs.post?.published.as_ref()),
+            Field::Added => self.test_date(attrs.post?.added.as_ref()),
+            Field::Edited => self.test_date(attrs.post?.edited.as_ref()),
+            Field::Name => self.test_str(attrs.file_name?),
+            Field::Ext => self.test_str(extension(attrs.file_name?)),
+            Field::Size => match self.value {
+                Value::Size(v) => self.op.test(attrs.size?.cmp(&v)),
+                _ => false,
+            },
+            Field::Type => {
+                // the extension decides if it is in the table, else the MIME type from HEAD
+                let category = match attrs
+                    .file_name
+                    .and_then(|name| Category::from_ext(extension(name)))
+                {
+                    Some(category) => Some(category),
+                    None => Category::from_mime(attrs.mime?),
+                };
+                let matched = match &self.value {
+                    Value::Categories(categories) => {
+                        category.is_some_and(|category| categories.contains(&category))
+                    }
+                    _ => false,
+                };
+                matched != (self.op == Op::Ne)
+            }
+        })
+    }
+}
+
+impl Expr {
+    fn reduce(&self, attrs: &Attrs<'_>) -> Expr {
+        match self {
+            Expr::Const(b) => Expr::Const(*b),
+            Expr::Cmp(cmp) => match cmp.eval(attrs) {
+                Some(b) => Expr::Const(b),
+                None => Expr::Cmp(cmp.clone()),
+            },
+            Expr::Not(inner) => match inner.reduce(attrs) {
+                Expr::Const(b) => Expr::Const(!b),
+                inner => Expr::Not(Box::new(inner)),
+            },
+            Expr::And(exprs) => reduce_all(exprs, attrs, false).unwrap_or_else(Expr::And),
+            Expr::Or(exprs) => reduce_all(exprs, attrs, true).unwrap_or_else(Expr::Or),
+        }
+    }
+
+    fn needs_post(&self) -> bool {
+        match self {
+            Expr::Const(_) => false,
+            Expr::Not(inner) => inner.needs_post(),
+            Expr::And(exprs) | Expr::Or(exprs) => exprs.iter().any(Expr::needs_post),
+            Expr::Cmp(cmp) => cmp.field.is_post(),
+        }
+    }
+}
+
+/// Reduce the operands of an `and` (`short` false) or `or` (`short` true)
+fn reduce_all(exprs: &[Expr], attrs: &Attrs<'_>, short: bool) -> Result<Expr, Vec<Expr>> {
+    let mut rest = Vec::new();
+    for expr in exprs {
+        match expr.reduce(attrs) {
+            Expr::Const(b) if b == short => return Ok(Expr::Const(short)),
+            Expr::Const(_) => {}
+            expr => rest.push(expr),
+        }
+    }
+    match rest.len() {
+        0 => Ok(Expr::Const(!short)),
+        1 => Ok(rest.pop().unwrap()),
+        _ => Err(rest),
+    }
+}
+
+impl Default for Filter {
+    /// Accepts everything
+    fn default() -> Self {
+        Filter {
+            expr: Expr::Const(true),
+        }
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            anyhow::bail!("unexpected {token:?} in filter");
+        }
+        Ok(Filter { expr })
+    }
+}
+
+impl Filter {
+    /// The `-w/-b` and `-W/-B` regex lists as a filter
+    ///
+    /// A whitelist passes if all of its regexes match, a blacklist rejects if all of its regexes match.
+    /// The regexes stay case-sensitive, unlike `~` in expressions.
+    pub fn from_regex_lists<'a>(
+        whitelist: impl Iterator<Item = &'a str>,
+        blacklist: impl Iterator<Item = &'a str>,
+        whitelist_filename: impl Iterator<Item = &'a str>,
+        blacklist_filename: impl Iterator<Item = &'a str>,
+    ) -> Result<Self> {
+        fn regexes<'a>(field: Field, res: impl Iterator<Item = &'a str>) -> Result<Vec<Expr>> {
+            res.map(|re| {
+                let value = Value::Regex(Regex::new(re)?);
+                Ok(Expr::Cmp(Cmp {
+                    field,
+                    op: Op::Match,
+                    value,
+                }))
+            })
+            .collect()
+        }
+        let mut exprs = regexes(Field::Title, whitelist)?;
+        exprs.extend(regexes(Field::Name, whitelist_filename)?);
+        for black in [
+            regexes(Field::Title, blacklist)?,
+            regexes(Field::Name, blacklist_filename)?,
+        ] {
+            if !black.is_empty() {
+                exprs.push(Expr::Not(Box::new(Expr::And(black))));
+            }
+        }
+        Ok(Filter {
+            expr: Expr::And(exprs),
+        })
+    }
+
+    /// Posts must have all of `tags` and none of `excluded`, compared case-insensitively
+    pub fn from_tags<'a>(
+        tags: impl Iterator<Item = &'a str>,
+        excluded: impl Iterator<Item = &'a str>,
+    ) -> Self {
+        let cmp = |op, tag: &str| {
+            Expr::Cmp(Cmp {
+                field: Field::Tag,
+                op,
+                value: Value::Str(tag.to_lowercase()),
+            })
+        };
+        let exprs = tags
+            .map(|tag| cmp(Op::Eq, tag))
+            .chain(excluded.map(|tag| cmp(Op::Ne, tag)))
+            .collect();
+        Filter {
+            expr: Expr::And(exprs),
+        }
+    }
+
+    /// Files must be of one of `categories`, if any, and within the size bounds
+    pub fn from_file_selection(
+        categories: &[Category],
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+    ) -> Self {
+        let mut exprs = Vec::new();
+        if !categories.is_empty() {
+            exprs.push(Expr::Cmp(Cmp {
+                field: Field::Type,
+                op: Op::In,
+                value: Value::Categories(categories.to_vec()),
+            }));
+        }
+        let bounds = [(Op::Ge, min_size), (Op::Le, max_size)];
+        for (op, size) in bounds {
+            if let Some(size) = size {
+                exprs.push(Expr::Cmp(Cmp {
+                    field: Field::Size,
+                    op,
+                    value: Value::Size(size),
+                }));
+            }
+        }
+        Filter {
+            expr: Expr::And(exprs),
+        }
+    }
+
+    /// Both filters must accept
+    pub fn and(self, other: Filter) -> Filter {
+        Filter {
+            expr: Expr::And(vec![self.expr, other.expr]),
+        }
+    }
+
+    /// Decide what can be decided with `attrs`, the result only depends on the unknown attributes
+    pub fn reduce(&self, attrs: &Attrs<'_>) -> Filter {
+        Filter {
+            expr: self.expr.reduce(attrs),
+        }
+    }
+
+    /// False only if no value of the unknown attributes can make the filter pass
+    pub fn may_pass(&self) -> bool {
+        self.expr != Expr::Const(false)
+    }
+
+    /// True if the filter passes whatever the unknown attributes are
+    pub fn passes(&self) -> bool {
+        self.expr == Expr::Const(true)
+    }
+
+    /// Whether what is left still depends on post attributes
+    pub fn needs_post(&self) -> bool {
+        self.expr.needs_post()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(Op),
+    Str(String),
+    Word(String),
+    List(Vec<String>),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            _ if ch.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(match ch {
+                    '(' => Token::LParen,
+                    _ => Token::RParen,
+                });
+            }
+            '[' => {
+                chars.next();
+                let mut items = Vec::new();
+                let mut item = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(',') => items.push(std::mem::take(&mut item)),
+                        Some('"') => item.push_str(&quoted(&mut chars)?),
+                        Some(ch) => item.push(ch),
+                        None => anyhow::bail!("unclosed [ in filter"),
+                    }
+                }
+                items.push(item);
+                let items = items
+                    .into_iter()
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect();
+                tokens.push(Token::List(items));
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Str(quoted(&mut chars)?));
+            }
+            '=' | '!' | '<' | '>' | '~' => {
+                chars.next();
+                let next = chars.peek().copied();
+                let op = match (ch, next) {
+                    ('=', Some('='))
+                    | ('!', Some('='))
+                    | ('!', Some('~'))
+                    | ('<', Some('='))
+                    | ('>', Some('=')) => {
+                        chars.next();
+                        match (ch, next) {
+                            ('=', _) => Op::Eq,
+                            ('!', Some('=')) => Op::Ne,
+                            ('!', _) => Op::NotMatch,
+                            ('<', _) => Op::Le,
+                            _ => Op::Ge,
+                        }
+                    }
+                    ('=', _) => Op::Eq,
+                    ('<', _) => Op::Lt,
+                    ('>', _) => Op::Gt,
+                    ('~', _) => Op::Match,
+                    _ => anyhow::bail!("unexpected ! in filter"),
+                };
+                tokens.push(Token::Op(op));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || "()[]\"=!<>~".contains(ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "in" => Token::Op(Op::In),
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Rest of a `"` quoted string, `\"` and `\\` are escapes
+fn quoted(chars: &mut impl Iterator<Item = char>) -> Result<String> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some(ch @ ('"' | '\\')) => s.push(ch),
+                Some(ch) => {
+                    // keep regex escapes like \d intact
+                    s.push('\\');
+                    s.push(ch);
+                }
+                None => break,
+            },
+            Some(ch) => s.push(ch),
+            None => break,
+        }
+    }
+    anyhow::bail!("unclosed \" in filter")
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Word(word)) if word == keyword) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut exprs = vec![self.and()?];
+        while self.keyword("or") {
+            exprs.push(self.and()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.pop().unwrap(),
+            _ => Expr::Or(exprs),
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut exprs = vec![self.not()?];
+        while self.keyword("and") {
+            exprs.push(self.not()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.pop().unwrap(),
+            _ => Expr::And(exprs),
+        })
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => anyhow::bail!("missing ) in filter"),
+                }
+            }
+            Some(Token::Word(name)) => {
+                let field =
+                    Field::parse(&name).ok_or_else(|| anyhow!("unknown filter field {name}"))?;
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => anyhow::bail!("expected an operator after {name}"),
+                };
+                let value = self
+                    .next()
+                    .ok_or_else(|| anyhow!("expected a value after {name} {op}"))?;
+                Ok(Expr::Cmp(Cmp::new(field, op, value)?))
+            }
+            Some(token) => anyhow::bail!("unexpected {token:?} in filter"),
+            None => anyhow::bail!("unexpected end of filter"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::RegexSet;
+
+    use super::*;
+    use crate::utils::whiteblack_regex_filter;
+
+    fn post(title: &str, tags: &[&str]) -> Post {
+        Post {
+            title: title.into(),
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            published: Some(Timestamp::parse("2024-01-31T15:30:00")),
+            ..Default::default()
+        }
+    }
+
+    /// Whether `expr` accepts the post with everything else unknown
+    fn accepts(expr: &str, post: &Post) -> bool {
+        let filter: Filter = expr.parse().unwrap();
+        filter
+            .reduce(&Attrs {
+                post: Some(post),
+                ..Default::default()
+            })
+            .may_pass()
+    }
+
+    fn accepts_file(expr: &str, file_name: &str) -> bool {
+        let filter: Filter = expr.parse().unwrap();
+        filter
+            .reduce(&Attrs {
+                file_name: Some(file_name),
+                ..Default::default()
+            })
+            .may_pass()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let p = post("melody", &[]);
+        assert!(accepts(
+            r#"title == "x" and title == "y" or title == "melody""#,
+            &p
+        ));
+        assert!(!accepts(
+            r#"title == "melody" and (title == "x" or title == "y")"#,
+            &p
+        ));
+        assert!(accepts(
+            r#"title == "x" or title == "y" or title == "melody""#,
+            &p
+        ));
+    }
+
+    #[test]
+    fn not_applies_to_the_next_term() {
+        let p = post("melody", &["wip"]);
+        assert!(!accepts(r#"not tag == "wip""#, &p));
+        assert!(accepts(r#"not tag == "wip" or title == "melody""#, &p));
+        assert!(!accepts(r#"not (tag == "wip" or title == "x")"#, &p));
+        assert!(accepts(r#"not not tag == "WIP""#, &p));
+    }
+
+    #[test]
+    fn in_lists() {
+        assert!(accepts_file("ext in [mp4, m4v]", "clip.M4V"));
+        assert!(!accepts_file("ext in [mp4, m4v]", "clip.png"));
+        assert!(accepts_file(r#"name in ["a, b.png", c.png]"#, "a, b.png"));
+        assert!(!accepts_file("ext in []", "clip.mp4"));
+    }
+
+    #[test]
+    fn quoting_and_escapes() {
+        let p = post(r#"say "hi" \ bye"#, &[]);
+        assert!(accepts(r#"title == "say \"hi\" \\ bye""#, &p));
+        // unknown escapes are kept for the regex
+        assert!(accepts_file(r#"name ~ "^\d+\.png$""#, "01.png"));
+        assert!(!accepts_file(r#"name ~ "^\d+\.png$""#, "a1.png"));
+        assert!(r#"title == "open"#.parse::<Filter>().is_err());
+        assert!("title ==".parse::<Filter>().is_err());
+        assert!("(title == a".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn matching_is_case_insensitive() {
+        let p = post("Melody", &["WIP"]);
+        assert!(accepts("title ~ melody", &p));
+        assert!(accepts("title == MELODY", &p));
+        assert!(accepts("tag in [wip]", &p));
+        assert!(!accepts("title !~ MEL", &p));
+    }
+
+    #[test]
+    fn dates_compare_by_day() {
+        let p = post("", &[]);
+        assert!(accepts("published == 2024-01-31", &p));
+        assert!(accepts(
+            "published >= 2024-01-31 and published <= 2024-01-31",
+            &p
+        ));
+        assert!(!accepts("published > 2024-01-31", &p));
+        assert!(accepts("published < 2024-02-01", &p));
+        assert!(!accepts("published > 30d", &p));
+    }
+
+    #[test]
+    fn unknown_attributes_stay_undecided() {
+        let filter: Filter = "size > 1M and ext == mp4".parse().unwrap();
+        let named = filter.reduce(&Attrs {
+            file_name: Some("a.mp4"),
+            ..Default::default()
+        });
+        assert!(named.may_pass() && !named.passes());
+        let sized = |size| {
+            named.reduce(&Attrs {
+                size: Some(size),
+                ..Default::default()
+            })
+        };
+        assert!(sized(2 << 20).passes());
+        assert!(!sized(1 << 10).may_pass());
+    }
+
+    #[test]
+    fn listing_entries_leave_tags_and_content_undecided() {
+        let entry = Post {
+            title: "melody".into(),
+            ..Default::default()
+        };
+        let listing = Attrs {
+            post: Some(&entry),
+            listing: true,
+            ..Default::default()
+        };
+        for expr in ["tag == wip", "tag != wip", r#"content ~ "x""#] {
+            let filter: Filter = expr.parse().unwrap();
+            let reduced = filter.reduce(&listing);
+            assert!(reduced.may_pass() && reduced.needs_post(), "{expr}");
+        }
+        let filter: Filter = "title == melody and ext == mp4".parse().unwrap();
+        let reduced = filter.reduce(&listing);
+        assert!(reduced.may_pass() && !reduced.needs_post());
+        assert!(!accepts("tag == wip", &entry));
+    }
+
+    #[test]
+    fn regex_lists_match_whiteblack_regex_filter() {
+        let lists: &[(&[&str], &[&str])] = &[
+            (&[], &[]),
+            (&["melody"], &[]),
+            (&[], &["wip"]),
+            (&["melody", "song"], &["wip"]),
+            (&["melody"], &["wip", "draft"]),
+        ];
+        let titles = [
+            "melody song",
+            "melody wip",
+            "wip draft",
+            "melody wip draft",
+            "Melody",
+            "",
+        ];
+        for (white, black) in lists {
+            let title_filter = Filter::from_regex_lists(
+                white.iter().copied(),
+                black.iter().copied(),
+                [].into_iter(),
+                [].into_iter(),
+            )
+            .unwrap();
+            let name_filter = Filter::from_regex_lists(
+                [].into_iter(),
+                [].into_iter(),
+                white.iter().copied(),
+                black.iter().copied(),
+            )
+            .unwrap();
+            let white_set = RegexSet::new(*white).unwrap();
+            let black_set = RegexSet::new(*black).unwrap();
+            for title in titles {
+                let expected = whiteblack_regex_filter(&white_set, &black_set, title);
+                let p = post(title, &[]);
+                let by_title = title_filter.reduce(&Attrs {
+                    post: Some(&p),
+                    ..Default::default()
+                });
+                let by_name = name_filter.reduce(&Attrs {
+                    file_name: Some(title),
+                    ..Default::default()
+                });
+                assert_eq!(by_title.passes(), expected, "{white:?} {black:?} {title}");
+                assert_eq!(by_name.passes(), expected, "{white:?} {black:?} {title}");
+            }
+        }
+    }
+}
@@ -1,60 +1,82 @@
 use std::sync::atomic::Ordering;
 
 use anyhow::{anyhow, Result};
+use futures_lite::StreamExt;
 
-use kemono_api::model::posts_legacy::{PostsLegacy, Props, Result as PLResult};
-use kemono_api::API;
-use tracing::{debug, error};
+use kemono_api::{Error, API};
+use tracing::{debug, error, info, warn};
 
-use crate::helper::post;
-use crate::utils::normalize_pathname;
+use crate::filter::Filter;
+use crate::helper::post::{self, compile_filter, Queue};
 use crate::DONE;
 
 use crate::helper::ctx;
-use crate::helper::utils::get_author_name;
+use crate::helper::utils::{get_author, new_api, Author};
 
 pub async fn download_all(ctx: impl ctx::Context<'_>) -> Result<()> {
-    let web_name = ctx.web_name();
+    let service = ctx.service();
     let user_id = ctx.user_id();
-    let base_url = ctx.api_base_url();
 
-    let api = API::try_with_base_url(base_url)?;
-    let mut offset = 0;
+    let api = new_api(&ctx)?;
+    let author = get_author(&api, service, user_id).await?;
+    let filter = compile_filter(&ctx)?;
 
-    loop {
+    let queue = Queue::new(ctx.max_concurrency());
+    let result = queue_posts(&ctx, &api, &queue, &author, &filter).await;
+    // let files already queued finish even if listing the posts failed
+    queue.finish().await;
+    result?;
+
+    if DONE.load(Ordering::Relaxed) {
+        anyhow::bail!("Received SIGINT, exiting!");
+    }
+    Ok(())
+}
+
+async fn queue_posts(
+    ctx: &impl ctx::Context<'_>,
+    api: &API,
+    queue: &Queue,
+    author: &Author,
+    filter: &Filter,
+) -> Result<()> {
+    let service = ctx.service();
+    let user_id = ctx.user_id();
+
+    let posts = api.posts(service, user_id);
+    futures_lite::pin!(posts);
+
+    while let Some(post) = posts.next().await {
         if DONE.load(Ordering::Relaxed) {
+            error!("Received SIGINT, exiting");
             break;
         }
 
-        let PostsLegacy {
-            props: Props { count, limit },
-            results,
-        } = api
-            .get_posts_legacy(web_name, user_id, offset)
-            .await
-            .map_err(|e| anyhow!("failed to fetch props: {e}"))?;
-
-        debug!("count: {count}, limit: {limit}");
-
-        let author = get_author_name(&api, web_name, user_id).await?;
-        let author = normalize_pathname(&author);
-
-        for PLResult {
-            id: ref post_id,
-            title: ref post_title,
-        } in results
-        {
-            if DONE.load(Ordering::Relaxed) {
-                error!("Received SIGINT, exiting");
-                break;
+        let post = match post {
+            Ok(post) => post,
+            Err(Error::NotFound { .. }) => {
+                anyhow::bail!("creator {service}/{user_id} not found");
             }
-            post::download_post(&ctx, &api, &post_id, &post_title, &author).await?;
-        }
+            Err(e) => return Err(anyhow!("failed to fetch posts: {e}")),
+        };
 
-        offset += limit;
-        if offset > count {
+        let date_range = ctx.date_range();
+        if date_range.is_past(&post) {
+            info!("Reached posts before --since, stopped listing");
             break;
         }
+        if !date_range.contains(&post) {
+            debug!("Skipped {} by date", post.title);
+            continue;
+        }
+
+        let post_id = post.id.clone();
+        if let Err(e) = post::download_post(ctx, api, queue, post, author, filter).await {
+            match e.downcast_ref::<Error>() {
+                Some(Error::NotFound { .. }) => warn!("skipped post {post_id}: {e:#}"),
+                _ => return Err(e),
+            }
+        }
     }
 
     Ok(())
@@ -1,27 +1,87 @@
-use std::path::PathBuf;
+use std::{net::IpAddr, path::PathBuf, time::Duration};
 
 use derive_builder::Builder;
+use kemono_api::{CreatorId, IpVersion, Service};
+
+use crate::{
+    category::Category,
+    dates::DateRange,
+    filter::Filter,
+    helper::post::{ExportFormat, NameCollision, PostDirStyle, Template},
+    mtime::MtimeSource,
+    utils::UnicodeForm,
+};
 
 pub trait Context<'a> {
-    fn web_name(&self) -> &'a str;
-    fn user_id(&self) -> &'a str;
+    fn service(&self) -> &'a Service;
+    fn user_id(&self) -> &'a CreatorId;
     fn output_dir(&self) -> &'a PathBuf;
     fn max_concurrency(&self) -> usize;
     fn whitelist_regexes(&self) -> impl Iterator<Item = &'a str>;
     fn blacklist_regexes(&self) -> impl Iterator<Item = &'a str>;
     fn whitelist_filename_regexes(&self) -> impl Iterator<Item = &'a str>;
     fn blacklist_filename_regexes(&self) -> impl Iterator<Item = &'a str>;
+    /// Posts must have all of these tags
+    fn tags(&self) -> impl Iterator<Item = &'a str>;
+    /// Posts with any of these tags are skipped
+    fn excluded_tags(&self) -> impl Iterator<Item = &'a str>;
     /// Base url of the kemono-compatible API.
     ///
     /// Example: https://kemono.su, https://coomer.su
     fn api_base_url(&self) -> &'a str;
+    /// Number of times a failed request is sent again
+    fn max_retries(&self) -> u32;
+    /// Initial delay between retries, doubled on every further attempt
+    fn retry_delay(&self) -> Duration;
+    /// Proxy url for all requests, e.g. socks5://127.0.0.1:1080
+    fn proxy(&self) -> Option<&'a str>;
+    fn user_agent(&self) -> Option<&'a str>;
+    /// Extra request headers in `Name: Value` form
+    fn headers(&self) -> impl Iterator<Item = &'a str>;
+    fn connect_timeout(&self) -> Option<Duration>;
+    fn read_timeout(&self) -> Option<Duration>;
+    fn bind_address(&self) -> Option<IpAddr>;
+    fn ip_version(&self) -> Option<IpVersion>;
+    /// Write a readable copy of each post next to `metadata.json`
+    fn export_format(&self) -> Option<ExportFormat>;
+    /// Parallel connections per large file, 1 disables segmented downloads
+    fn segments(&self) -> u64;
+    /// Minimum file size in bytes for a segmented download
+    fn segment_threshold(&self) -> u64;
+    /// Check downloaded files against the SHA-256 in their data path
+    fn verify(&self) -> bool;
+    /// Keep files in a shared store under the output dir and link them into posts
+    fn use_store(&self) -> bool;
+    /// Set mtimes of post directories and files from this post date
+    fn mtime_source(&self) -> Option<MtimeSource>;
+    /// How different files of a post with the same name are saved
+    fn name_collision(&self) -> NameCollision;
+    fn post_dir_style(&self) -> PostDirStyle;
+    /// Rename directories named by title only to the current style
+    fn migrate_legacy_dirs(&self) -> bool;
+    /// Post directory relative to the output dir, replaces the author and post dir style
+    fn path_template(&self) -> Option<&'a Template>;
+    fn filename_template(&self) -> Option<&'a Template>;
+    /// Unicode normalization of directory and file names
+    fn unicode_form(&self) -> UnicodeForm;
+    /// Expression posts and files must match, on top of the regex lists
+    fn filter(&self) -> Option<&'a Filter>;
+    /// Posts outside of this range are skipped
+    fn date_range(&self) -> &'a DateRange;
+    /// File categories to download, all if empty
+    fn only(&self) -> &'a [Category];
+    /// Size bounds in bytes, checked against the HEAD response before downloading
+    fn min_size(&self) -> Option<u64>;
+    fn max_size(&self) -> Option<u64>;
 }
 
 #[derive(Clone, Builder, PartialEq, Eq, Default)]
 pub struct Args {
-    web_name: String,
-    user_id: String,
+    service: Service,
+    user_id: CreatorId,
+    #[builder(default = "PathBuf::from(\"./download\")")]
     output_dir: PathBuf,
+    #[builder(default = "4")]
     max_concurrency: usize,
     #[builder(default = "Vec::new()")]
     whitelist_regexes: Vec<String>,
@@ -31,8 +91,64 @@ pub struct Args {
     whitelist_filename_regexes: Vec<String>,
     #[builder(default = "Vec::new()")]
     blacklist_filename_regexes: Vec<String>,
+    #[builder(default = "Vec::new()")]
+    tags: Vec<String>,
+    #[builder(default = "Vec::new()")]
+    excluded_tags: Vec<String>,
     #[builder(default = "String::from(\"https://kemono.su\")")]
     api_base_url: String,
+    #[builder(default = "4")]
+    max_retries: u32,
+    #[builder(default = "Duration::from_secs(1)")]
+    retry_delay: Duration,
+    #[builder(default)]
+    proxy: Option<String>,
+    #[builder(default)]
+    user_agent: Option<String>,
+    #[builder(default = "Vec::new()")]
+    headers: Vec<String>,
+    #[builder(default)]
+    connect_timeout: Option<Duration>,
+    #[builder(default)]
+    read_timeout: Option<Duration>,
+    #[builder(default)]
+    bind_address: Option<IpAddr>,
+    #[builder(default)]
+    ip_version: Option<IpVersion>,
+    #[builder(default)]
+    export_format: Option<ExportFormat>,
+    #[builder(default = "1")]
+    segments: u64,
+    #[builder(default = "64 * 1024 * 1024")]
+    segment_threshold: u64,
+    #[builder(default = "true")]
+    verify: bool,
+    #[builder(default)]
+    use_store: bool,
+    #[builder(default)]
+    mtime_source: Option<MtimeSource>,
+    #[builder(default)]
+    name_collision: NameCollision,
+    #[builder(default)]
+    post_dir_style: PostDirStyle,
+    #[builder(default)]
+    migrate_legacy_dirs: bool,
+    #[builder(default)]
+    path_template: Option<Template>,
+    #[builder(default)]
+    filename_template: Option<Template>,
+    #[builder(default)]
+    unicode_form: UnicodeForm,
+    #[builder(default)]
+    filter: Option<Filter>,
+    #[builder(default)]
+    date_range: DateRange,
+    #[builder(default = "Vec::new()")]
+    only: Vec<Category>,
+    #[builder(default)]
+    min_size: Option<u64>,
+    #[builder(default)]
+    max_size: Option<u64>,
 }
 
 impl Args {
@@ -42,11 +158,11 @@ impl Args {
 }
 
 impl<'a> Context<'a> for &'a Args {
-    fn web_name(&self) -> &'a str {
-        &self.web_name
+    fn service(&self) -> &'a Service {
+        &self.service
     }
 
-    fn user_id(&self) -> &'a str {
+    fn user_id(&self) -> &'a CreatorId {
         &self.user_id
     }
 
@@ -74,7 +190,119 @@ impl<'a> Context<'a> for &'a Args {
         self.blacklist_filename_regexes.iter().map(String::as_str)
     }
 
+    fn tags(&self) -> impl Iterator<Item = &'a str> {
+        self.tags.iter().map(String::as_str)
+    }
+
+    fn excluded_tags(&self) -> impl Iterator<Item = &'a str> {
+        self.excluded_tags.iter().map(String::as_str)
+    }
+
     fn api_base_url(&self) -> &'a str {
         &self.api_base_url
     }
+
+    fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    fn retry_delay(&self) -> Duration {
+        self.retry_delay
+    }
+
+    fn proxy(&self) -> Option<&'a str> {
+        self.proxy.as_deref()
+    }
+
+    fn user_agent(&self) -> Option<&'a str> {
+        self.user_agent.as_deref()
+    }
+
+    fn headers(&self) -> impl Iterator<Item = &'a str> {
+        self.headers.iter().map(String::as_str)
+    }
+
+    fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    fn bind_address(&self) -> Option<IpAddr> {
+        self.bind_address
+    }
+
+    fn ip_version(&self) -> Option<IpVersion> {
+        self.ip_version
+    }
+
+    fn export_format(&self) -> Option<ExportFormat> {
+        self.export_format
+    }
+
+    fn segments(&self) -> u64 {
+        self.segments
+    }
+
+    fn segment_threshold(&self) -> u64 {
+        self.segment_threshold
+    }
+
+    fn verify(&self) -> bool {
+        self.verify
+    }
+
+    fn use_store(&self) -> bool {
+        self.use_store
+    }
+
+    fn mtime_source(&self) -> Option<MtimeSource> {
+        self.mtime_source
+    }
+
+    fn name_collision(&self) -> NameCollision {
+        self.name_collision
+    }
+
+    fn post_dir_style(&self) -> PostDirStyle {
+        self.post_dir_style
+    }
+
+    fn migrate_legacy_dirs(&self) -> bool {
+        self.migrate_legacy_dirs
+    }
+
+    fn path_template(&self) -> Option<&'a Template> {
+        self.path_template.as_ref()
+    }
+
+    fn filename_template(&self) -> Option<&'a Template> {
+        self.filename_template.as_ref()
+    }
+
+    fn unicode_form(&self) -> UnicodeForm {
+        self.unicode_form
+    }
+
+    fn filter(&self) -> Option<&'a Filter> {
+        self.filter.as_ref()
+    }
+
+    fn date_range(&self) -> &'a DateRange {
+        &self.date_range
+    }
+
+    fn only(&self) -> &'a [Category] {
+        &self.only
+    }
+
+    fn min_size(&self) -> Option<u64> {
+        self.min_size
+    }
+
+    fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
 }
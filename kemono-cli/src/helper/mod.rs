@@ -1,5 +1,6 @@
 pub mod batch;
 pub mod single;
+pub mod tags;
 
 pub mod ctx;
 pub mod post;
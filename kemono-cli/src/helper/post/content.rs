@@ -0,0 +1,94 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use kemono_api::reqwest::Url;
+use regex::{Captures, Regex};
+
+/// `src`/`href` attributes of tags that may point at kemono-hosted media
+static MEDIA_ATTR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)(<(?:img|a|video|audio|source)\b[^>]*?\s(?:src|href)\s*=\s*)(["'])([^"']*)(["'])"#,
+    )
+    .expect("failed to compile regex")
+});
+
+/// A media file referenced from the post body
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineMedia {
+    /// Attribute value as written in the HTML
+    pub reference: String,
+    /// File server, `None` for references relative to the site
+    pub server: Option<String>,
+    /// Path below `/data`, e.g. `/ab/cd/<sha256>.png`
+    pub path: String,
+    pub name: String,
+}
+
+/// Collect media hosted on `base_url` (or its file servers) from the post body
+pub fn extract_media(content: &str, base_url: &str) -> Vec<InlineMedia> {
+    let Ok(base_url) = Url::parse(base_url) else {
+        return Vec::new();
+    };
+    MEDIA_ATTR
+        .captures_iter(content)
+        .filter_map(|caps| parse_reference(&caps[3], &base_url))
+        .collect()
+}
+
+fn parse_reference(reference: &str, base_url: &Url) -> Option<InlineMedia> {
+    let url = base_url.join(&reference.replace("&amp;", "&")).ok()?;
+    let host = url.host_str()?;
+    let site = base_url.host_str()?;
+    let site = site.strip_prefix("www.").unwrap_or(site);
+    if host != site && !host.ends_with(&format!(".{site}")) {
+        return None;
+    }
+
+    let path = url.path().strip_prefix("/data")?;
+    let file_name = path.rsplit('/').next().filter(|s| !s.is_empty())?;
+    let name = url
+        .query_pairs()
+        .find(|(k, _)| k == "f")
+        .map(|(_, v)| v.into_owned())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| file_name.to_string());
+
+    let server = (host != site).then(|| url.origin().ascii_serialization());
+
+    Some(InlineMedia {
+        reference: reference.to_string(),
+        server,
+        path: path.to_string(),
+        name,
+    })
+}
+
+/// Point references found in `local_names` at the downloaded files, keyed by the original attribute value
+pub fn rewrite_content(content: &str, local_names: &HashMap<&str, &str>) -> String {
+    MEDIA_ATTR
+        .replace_all(content, |caps: &Captures| match local_names.get(&caps[3]) {
+            Some(local) => format!("{}{}{}{}", &caps[1], &caps[2], encode_path(local), &caps[4]),
+            None => caps[0].to_string(),
+        })
+        .into_owned()
+}
+
+/// Percent-encode the characters that would break a relative URL in an attribute
+pub fn encode_path(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for ch in name.chars() {
+        match ch {
+            '%' | ' ' | '"' | '\'' | '#' | '?' | '<' | '>' => {
+                encoded.push_str(&format!("%{:02X}", ch as u32))
+            }
+            _ => encoded.push(ch),
+        }
+    }
+    encoded
+}
+
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
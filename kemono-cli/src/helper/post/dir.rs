@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use kemono_api::model::post_info::Post;
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::utils::{legacy_pathname, normalize_pathname, UnicodeForm};
+
+/// How the directory of a post is named inside the author directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PostDirStyle {
+    /// `Title [id]`
+    #[default]
+    IdSuffix,
+    /// `[id] Title`
+    IdPrefix,
+    /// `2024-01-31 Title`, not unique for same-titled posts of one day
+    Date,
+    /// `Title`, as in older versions, posts with the same title share a directory
+    Title,
+}
+
+/// Directory name of a post, falling back to the post id for empty titles
+pub fn dir_name(post: &Post, style: PostDirStyle, form: UnicodeForm) -> String {
+    // normalized again as the decorations may push the name over the length limit
+    normalize_pathname(&decorated_name(post, style, form), form)
+}
+
+fn decorated_name(post: &Post, style: PostDirStyle, form: UnicodeForm) -> String {
+    let title = normalize_pathname(&post.title, form);
+    let id = normalize_pathname(post.id.as_str(), form);
+    if title.is_empty() {
+        return match post.published.as_ref().and_then(|ts| ts.datetime()) {
+            Some(published) if style == PostDirStyle::Date => {
+                format!("{} {id}", published.format("%Y-%m-%d"))
+            }
+            _ => id,
+        };
+    }
+    match style {
+        PostDirStyle::IdSuffix => format!("{title} [{id}]"),
+        PostDirStyle::IdPrefix => format!("[{id}] {title}"),
+        PostDirStyle::Date => match post.published.as_ref().and_then(|ts| ts.datetime()) {
+            Some(published) => format!("{} {title}", published.format("%Y-%m-%d")),
+            None => format!("{title} [{id}]"),
+        },
+        PostDirStyle::Title => title,
+    }
+}
+
+/// Directory name used before post directories were made unique
+pub fn legacy_dir_name(post: &Post) -> String {
+    match post.title.as_str() {
+        "" => legacy_pathname(post.id.as_str()),
+        title => legacy_pathname(title),
+    }
+}
+
+/// Rename the legacy directory of `post` to `new_dir`
+///
+/// Only done if the legacy `metadata.json` belongs to this post, directories merged from
+/// several posts are left alone as their files cannot be told apart.
+pub async fn migrate_legacy_dir(post: &Post, legacy_dir: &Path, new_dir: &Path) {
+    if legacy_dir == new_dir || new_dir.exists() || !legacy_dir.is_dir() {
+        return;
+    }
+    let owner = fs::read_to_string(legacy_dir.join("metadata.json"))
+        .await
+        .ok()
+        .and_then(|json| kemono_api::serde_json::from_str::<Post>(&json).ok());
+    match owner {
+        Some(owner) if owner.id == post.id => match rename(legacy_dir, new_dir).await {
+            Ok(()) => info!("migrated {} to {}", legacy_dir.display(), new_dir.display()),
+            Err(e) => warn!("failed to migrate {}: {e}", legacy_dir.display()),
+        },
+        Some(owner) => warn!(
+            "not migrating {}, it holds post {} instead of {}",
+            legacy_dir.display(),
+            owner.id,
+            post.id
+        ),
+        None => warn!(
+            "not migrating {}, its metadata.json is missing or invalid",
+            legacy_dir.display()
+        ),
+    }
+}
+
+/// Rename, creating the parent of `to`, which differs when the author directory was renamed too
+async fn rename(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::rename(from, to).await
+}
@@ -0,0 +1,228 @@
+use std::fmt::Write;
+
+use kemono_api::{model::post_info::Post, Timestamp};
+
+use super::content::{encode_path, escape_html};
+use super::model::Attachment;
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "avif", "bmp"];
+
+/// Format of the readable post body written next to `metadata.json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// post.html
+    Html,
+    /// post.md
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Html => "post.html",
+            ExportFormat::Markdown => "post.md",
+        }
+    }
+}
+
+/// Render the post, `content` must already point at the local files
+pub fn render(
+    format: ExportFormat,
+    post: &Post,
+    content: &str,
+    attachments: &[Attachment<'_>],
+) -> String {
+    match format {
+        ExportFormat::Html => render_html(post, content, attachments),
+        ExportFormat::Markdown => render_markdown(post, content, attachments),
+    }
+}
+
+fn format_date(ts: &Option<Timestamp>) -> Option<String> {
+    let ts = ts.as_ref()?;
+    Some(match ts.datetime() {
+        Some(dt) => dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => ts.as_str().to_string(),
+    })
+}
+
+fn is_image(file_name: &str) -> bool {
+    file_name
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn render_html(post: &Post, content: &str, attachments: &[Attachment<'_>]) -> String {
+    let title = escape_html(&post.title);
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, "<html>\n<head>\n<meta charset=\"utf-8\">");
+    let _ = writeln!(html, "<title>{title}</title>\n</head>\n<body>");
+    let _ = writeln!(html, "<h1>{title}</h1>");
+
+    let mut info = Vec::new();
+    if let Some(published) = format_date(&post.published) {
+        info.push(format!("Published: {}", escape_html(&published)));
+    }
+    if let Some(edited) = format_date(&post.edited) {
+        info.push(format!("Edited: {}", escape_html(&edited)));
+    }
+    if let Some(tags) = post.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        info.push(format!("Tags: {}", escape_html(&tags.join(", "))));
+    }
+    if !info.is_empty() {
+        let _ = writeln!(html, "<p>{}</p>", info.join("<br>\n"));
+    }
+
+    if let Some(url) = &post.embed.url {
+        let subject = post.embed.subject.as_deref().unwrap_or(url);
+        let _ = writeln!(
+            html,
+            "<p>Embed: <a href=\"{}\">{}</a></p>",
+            escape_html(url),
+            escape_html(subject)
+        );
+        if let Some(description) = &post.embed.description {
+            let _ = writeln!(
+                html,
+                "<blockquote>{}</blockquote>",
+                escape_html(description)
+            );
+        }
+    }
+
+    let _ = writeln!(html, "<hr>\n{content}\n<hr>");
+
+    if let Some(poll) = &post.poll {
+        let _ = writeln!(html, "<h2>Poll: {}</h2>", escape_html(&poll.title));
+        if let Some(description) = &poll.description {
+            let _ = writeln!(html, "<p>{}</p>", escape_html(description));
+        }
+        let _ = writeln!(html, "<ul>");
+        for choice in &poll.choices {
+            let _ = writeln!(
+                html,
+                "<li>{} ({} votes)</li>",
+                escape_html(&choice.text),
+                choice.votes
+            );
+        }
+        let _ = writeln!(html, "</ul>\n<p>Total votes: {}</p>", poll.total_votes);
+    }
+
+    if !attachments.is_empty() {
+        let _ = writeln!(html, "<h2>Files</h2>\n<ul>");
+        for Attachment { file_name, .. } in attachments {
+            let href = encode_path(file_name);
+            let name = escape_html(file_name);
+            if is_image(file_name) {
+                let _ = writeln!(
+                    html,
+                    "<li><a href=\"{href}\"><img src=\"{href}\" alt=\"{name}\" style=\"max-width: 100%\"></a></li>"
+                );
+            } else {
+                let _ = writeln!(html, "<li><a href=\"{href}\">{name}</a></li>");
+            }
+        }
+        let _ = writeln!(html, "</ul>");
+    }
+
+    let _ = writeln!(html, "</body>\n</html>");
+    html
+}
+
+fn render_markdown(post: &Post, content: &str, attachments: &[Attachment<'_>]) -> String {
+    let mut md = String::new();
+    let _ = writeln!(md, "# {}\n", post.title);
+
+    if let Some(published) = format_date(&post.published) {
+        let _ = writeln!(md, "- Published: {published}");
+    }
+    if let Some(edited) = format_date(&post.edited) {
+        let _ = writeln!(md, "- Edited: {edited}");
+    }
+    if let Some(tags) = post.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        let _ = writeln!(md, "- Tags: {}", tags.join(", "));
+    }
+    if let Some(url) = &post.embed.url {
+        let subject = post.embed.subject.as_deref().unwrap_or(url);
+        let _ = writeln!(md, "- Embed: [{subject}](<{url}>)");
+    }
+    md.push('\n');
+
+    // Markdown renders inline HTML, so the body is kept as is
+    if !content.is_empty() {
+        let _ = writeln!(md, "{content}\n");
+    }
+
+    if let Some(poll) = &post.poll {
+        let _ = writeln!(md, "## Poll: {}\n", poll.title);
+        if let Some(description) = &poll.description {
+            let _ = writeln!(md, "{description}\n");
+        }
+        for choice in &poll.choices {
+            let _ = writeln!(md, "- {} ({} votes)", choice.text, choice.votes);
+        }
+        let _ = writeln!(md, "\nTotal votes: {}\n", poll.total_votes);
+    }
+
+    if !attachments.is_empty() {
+        let _ = writeln!(md, "## Files\n");
+        for Attachment { file_name, .. } in attachments {
+            let href = encode_path(file_name);
+            if is_image(file_name) {
+                let _ = writeln!(md, "- ![{file_name}]({href})");
+            } else {
+                let _ = writeln!(md, "- [{file_name}]({href})");
+            }
+        }
+    }
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use kemono_api::model::post_info::{Choice, Embed, Poll};
+
+    use super::*;
+
+    fn full_post() -> Post {
+        Post {
+            title: "melody".into(),
+            content: "<p>body</p>".into(),
+            tags: Some(vec!["wip".into(), "sketch".into()]),
+            embed: Embed {
+                url: Some("https://example.com/v".into()),
+                subject: Some("video".into()),
+                description: None,
+            },
+            poll: Some(Poll {
+                title: "next?".into(),
+                choices: vec![Choice {
+                    text: "more".into(),
+                    votes: 3,
+                }],
+                total_votes: 3,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exports_render_the_full_post() {
+        let post = full_post();
+        for format in [ExportFormat::Html, ExportFormat::Markdown] {
+            let body = render(format, &post, &post.content, &[]);
+            for part in [
+                "<p>body</p>",
+                "wip, sketch",
+                "https://example.com/v",
+                "next?",
+                "more (3 votes)",
+            ] {
+                assert!(body.contains(part), "{format:?} lacks {part}");
+            }
+        }
+    }
+}
@@ -1,93 +1,285 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::iter;
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 
-use anyhow::{anyhow, Result};
-use kemono_api::model::post_info::{AttachmentLike, Post, PostInfo};
-use regex::RegexSet;
+use anyhow::{Context as _, Result};
+use kemono_api::model::post_info::{AttachmentLike, File, Post, PostInfo};
 use tokio::fs;
-use tokio::task::JoinSet;
 use tracing::{debug, error, info, trace, warn};
 
 use kemono_api::API;
 
+use crate::filter::{Attrs, Filter};
 use crate::helper::ctx;
-use crate::utils::{normalize_pathname, whiteblack_regex_filter};
+use crate::helper::utils::Author;
+use crate::mtime;
+use crate::store::Store;
+use crate::utils::{normalize_pathname, DownloadOptions};
+use crate::verify::expected_hash;
 use crate::DONE;
 
+mod content;
+mod dir;
+pub use dir::PostDirStyle;
+mod export;
+pub use export::ExportFormat;
 mod model;
 use model::Attachment;
+mod naming;
+pub use naming::NameCollision;
+mod template;
+pub use template::{parse_path_template, Template};
 mod worker;
-use worker::{worker, Payload};
+use worker::Payload;
+pub(crate) use worker::Queue;
 
-#[tracing::instrument(skip(ctx, api))]
+/// The `--filter` expression combined with the regex and tag lists and the file selection
+///
+/// Compiled once per run and reduced as posts, file names and file sizes become known.
+pub(crate) fn compile_filter<'a>(ctx: &impl ctx::Context<'a>) -> Result<Filter> {
+    let filter = Filter::from_regex_lists(
+        ctx.whitelist_regexes(),
+        ctx.blacklist_regexes(),
+        ctx.whitelist_filename_regexes(),
+        ctx.blacklist_filename_regexes(),
+    )?
+    .and(Filter::from_tags(ctx.tags(), ctx.excluded_tags()))
+    .and(Filter::from_file_selection(
+        ctx.only(),
+        ctx.min_size(),
+        ctx.max_size(),
+    ));
+    Ok(match ctx.filter() {
+        Some(expr) => filter.and(expr.clone()),
+        None => filter,
+    })
+}
+
+/// The filter with the post attributes decided, `None` if the post is rejected
+pub(crate) fn post_filter(filter: &Filter, post: &Post) -> Option<Filter> {
+    let filter = filter.reduce(&Attrs {
+        post: Some(post),
+        ..Default::default()
+    });
+    filter.may_pass().then_some(filter)
+}
+
+/// Download a post taken from the creator's post listing
+///
+/// The full post info is only requested if the listing entry lacks fields needed to download
+/// it or to decide the filter
+#[tracing::instrument(skip_all, fields(post_id = %post.id, post_title = post.title))]
 pub(crate) async fn download_post(
     ctx: &impl ctx::Context<'_>,
     api: &API,
-    post_id: &str,
-    post_title: &str,
-    author: &str,
+    queue: &Queue,
+    post: Post,
+    author: &Author,
+    filter: &Filter,
 ) -> Result<()> {
-    let web_name = ctx.web_name();
-    let user_id = ctx.user_id();
-    let output_dir = ctx.output_dir();
+    let listed = filter.reduce(&Attrs {
+        post: Some(&post),
+        listing: true,
+        ..Default::default()
+    });
+    if !listed.may_pass() {
+        info!("Skipped {} by filter", post.title);
+        return Ok(());
+    }
 
-    let whitelist_regex = ctx.whitelist_regexes();
-    let blacklist_regex = ctx.blacklist_regexes();
-    let whitelist_regex = RegexSet::new(whitelist_regex)?;
-    let blacklist_regex = RegexSet::new(blacklist_regex)?;
+    // download_post_info decides the filter again with the full post
+    let post_info = if is_listing_complete(&post) && !listed.needs_post() {
+        from_listing(post)
+    } else {
+        debug!("listing entry incomplete or undecided by filter, fetching post info");
+        api.get_post_info(ctx.service(), ctx.user_id(), &post.id)
+            .await
+            .context("failed to get post info")?
+    };
 
-    let whitelist_filename_regex = ctx.whitelist_filename_regexes();
-    let blacklist_filename_regex = ctx.blacklist_filename_regexes();
-    let whitelist_filename_regex = RegexSet::new(whitelist_filename_regex)?;
-    let blacklist_filename_regex = RegexSet::new(blacklist_filename_regex)?;
+    download_post_info(ctx, api, queue, post_info, author, filter).await
+}
 
-    if !whiteblack_regex_filter(&whitelist_regex, &blacklist_regex, post_title) {
-        info!("Skipped {post_title} by filter");
-        return Ok(());
+/// Listing entries carry no `server`, which is fine, but files without name or path are not.
+/// Entries with a `substring` lack the content and more that metadata.json and exports keep
+fn is_listing_complete(post: &Post) -> bool {
+    post.substring.is_none()
+        && post
+            .attachments
+            .iter()
+            .all(|attach| attach.name.is_some() && attach.path.is_some())
+}
+
+fn from_listing(post: Post) -> PostInfo {
+    PostInfo {
+        attachments: post.attachments.clone(),
+        previews: Vec::new(),
+        post,
     }
+}
+
+#[tracing::instrument(skip_all, fields(post_id = %post_info.post.id))]
+pub(crate) async fn download_post_info(
+    ctx: &impl ctx::Context<'_>,
+    api: &API,
+    queue: &Queue,
+    post_info: PostInfo,
+    author: &Author,
+    filter: &Filter,
+) -> Result<()> {
+    let output_dir = ctx.output_dir();
+    let default_server = ctx.api_base_url();
 
     let PostInfo {
         post: metadata,
         attachments,
         previews,
-    } = api
-        .get_post_info(web_name, user_id, post_id)
-        .await
-        .map_err(|e| anyhow!("failed to get post info: {e}"))?;
+    } = post_info;
 
     trace!("metadata: {metadata:?}");
 
-    let post_dir = normalize_pathname(post_title);
-    let save_path = output_dir.join(&author).join(post_dir.as_str());
+    let form = ctx.unicode_form();
+    let author_dir = output_dir.join(author.dir_name(form));
+    let post_vars = template::Vars {
+        author,
+        post: &metadata,
+        file: None,
+    };
+    let save_path = match ctx.path_template() {
+        Some(template) => output_dir.join(template.render_path(&post_vars, form)),
+        None => author_dir.join(dir::dir_name(&metadata, ctx.post_dir_style(), form)),
+    };
+    if ctx.migrate_legacy_dirs() {
+        let legacy_path = output_dir
+            .join(author.legacy_dir_name())
+            .join(dir::legacy_dir_name(&metadata));
+        dir::migrate_legacy_dir(&metadata, &legacy_path, &save_path).await;
+    }
 
     info!("start");
 
-    let attachments = attachments
+    let File { name, path } = &metadata.file;
+    let main_file = AttachmentLike {
+        server: None,
+        name: name.clone(),
+        path: path.clone(),
+    };
+
+    let inline_media = content::extract_media(&metadata.content, default_server);
+    let inline_files = inline_media
         .iter()
-        .chain(previews.iter())
+        .map(|media| AttachmentLike {
+            server: media.server.clone(),
+            name: Some(media.name.clone()),
+            path: Some(media.path.clone()),
+        })
+        .collect::<Vec<_>>();
+
+    let Some(filter) = post_filter(filter, &metadata) else {
+        info!("Skipped {} by filter", metadata.title);
+        return Ok(());
+    };
+
+    // the main file usually shows up again in previews, where its server is known
+    let files = || {
+        attachments
+            .iter()
+            .chain(previews.iter())
+            .chain(iter::once(&main_file))
+            .chain(inline_files.iter())
+    };
+
+    // {index} follows the post's own order, main file then attachments, and is taken before
+    // filtering, so a file keeps its number whatever is filtered and however the post was fetched
+    let mut indices = HashMap::new();
+    let own_paths = iter::once(&metadata.file.path)
+        .chain(metadata.attachments.iter().map(|attach| &attach.path));
+    for path in own_paths
+        .chain(files().map(|attach| &attach.path))
+        .flatten()
+    {
+        let next = indices.len() + 1;
+        indices.entry(path.as_str()).or_insert(next);
+    }
+
+    // files are told apart by path only, same-named files are renamed by resolve_collisions
+    let mut seen_paths = HashSet::new();
+    let attachments = files()
         .filter_map(|attach| match attach {
             AttachmentLike {
-                server: Some(file_server),
+                server,
                 name: Some(file_name),
                 path: Some(file_path),
-            } if whiteblack_regex_filter(
-                &whitelist_filename_regex,
-                &blacklist_filename_regex,
-                file_name,
-            ) =>
-            {
+            } if seen_paths.insert(file_path.as_str()) => {
+                let filter = filter.reduce(&Attrs {
+                    file_name: Some(file_name),
+                    ..Default::default()
+                });
+                if !filter.may_pass() {
+                    debug!("Skipped {file_name} by filter");
+                    return None;
+                }
                 Some(Attachment {
-                    file_server,
-                    file_name,
+                    // kemono redirects /data on the main site to the right file server
+                    file_server: server.as_deref().unwrap_or(default_server),
+                    file_name: Cow::Owned(normalize_pathname(file_name, form)),
                     file_path,
+                    filter,
                 })
             }
             _ => None,
-        });
-    download_post_attachments(ctx, &save_path, &api, &metadata, attachments).await?;
+        })
+        .collect::<Vec<_>>();
+    let attachments = match ctx.filename_template() {
+        Some(template) => attachments
+            .into_iter()
+            .map(|mut attach| {
+                let vars = template::Vars {
+                    file: Some(template::FileVars {
+                        index: indices.get(attach.file_path).copied().unwrap_or_default(),
+                        name: &attach.file_name,
+                        path: attach.file_path,
+                    }),
+                    ..post_vars
+                };
+                let name = template.render_file_name(&vars, form);
+                if !name.is_empty() {
+                    attach.file_name = Cow::Owned(name);
+                }
+                attach
+            })
+            .collect(),
+        None => attachments,
+    };
+    let attachments = naming::resolve_collisions(attachments, ctx.name_collision());
+
+    let local_names = attachments
+        .iter()
+        .map(|attach| (attach.file_path, attach.file_name.as_ref()))
+        .collect::<HashMap<_, _>>();
+    let references = inline_media
+        .iter()
+        .filter_map(|media| {
+            let local = local_names.get(media.path.as_str())?;
+            Some((media.reference.as_str(), *local))
+        })
+        .collect::<HashMap<_, _>>();
+    let content = content::rewrite_content(&metadata.content, &references);
+
+    download_post_attachments(
+        ctx,
+        &save_path,
+        api,
+        queue,
+        &metadata,
+        &content,
+        &attachments,
+    )
+    .await?;
 
-    info!("completed");
+    info!("queued");
 
     Ok(())
 }
@@ -96,10 +288,19 @@ pub(super) async fn download_post_attachments(
     ctx: &impl ctx::Context<'_>,
     save_path: &PathBuf,
     api: &API,
+    queue: &Queue,
     metadata: &Post,
-    attachments: impl Iterator<Item = Attachment<'_>>,
+    content: &str,
+    attachments: &[Attachment<'_>],
 ) -> Result<()> {
-    let max_concurrency = ctx.max_concurrency() as u16;
+    let options = DownloadOptions {
+        segments: ctx.segments(),
+        segment_threshold: ctx.segment_threshold(),
+        verify: ctx.verify(),
+        store: ctx.use_store().then(|| Store::new(ctx.output_dir())),
+        mtime: ctx.mtime_source().and_then(|source| source.time(metadata)),
+        filter: Filter::default(),
+    };
 
     if DONE.load(Ordering::Relaxed) {
         return Ok(());
@@ -124,29 +325,66 @@ pub(super) async fn download_post_attachments(
         return Ok(());
     };
 
-    let mut set = HashSet::new();
-    let mut tasks = JoinSet::new();
+    if !content.is_empty() {
+        let content_path = save_path.join("content.html");
+        let html = format!(
+            "<!DOCTYPE html>\n<meta charset=\"utf-8\">\n<title>{}</title>\n{content}\n",
+            content::escape_html(&metadata.title)
+        );
+        if let Err(e) = fs::write(content_path, html).await {
+            error!("failed to write content: {e}");
+        }
+    }
 
-    let (tx, rx) = std::sync::mpmc::channel();
+    if let Some(format) = ctx.export_format() {
+        let export_path = save_path.join(format.file_name());
+        let body = export::render(format, metadata, content, attachments);
+        if let Err(e) = fs::write(export_path, body).await {
+            error!("failed to export post: {e}");
+        }
+    }
 
     for Attachment {
         file_server,
         file_name,
         file_path,
+        filter,
     } in attachments
     {
         if DONE.load(Ordering::Relaxed) {
-            tasks.join_all().await;
             anyhow::bail!("Received SIGINT, exiting!");
         }
 
-        if !set.insert(file_name) {
-            warn!("skipped duplicated file: {file_name}");
-            continue;
+        if let (Some(store), Some(hash)) = (&options.store, expected_hash(file_path)) {
+            let stored = store.size(&hash).map(|size| {
+                filter.reduce(&Attrs {
+                    size: Some(size),
+                    ..Default::default()
+                })
+            });
+            if stored.as_ref().is_some_and(|filter| !filter.may_pass()) {
+                info!("Skipped {file_name} by filter");
+                continue;
+            }
+            // a filter still undecided, e.g. on the type of an unknown extension, needs HEAD
+            if stored.is_some_and(|filter| filter.passes()) {
+                let dest = save_path.join(file_name.as_ref());
+                match store.link_into(&hash, &dest).await {
+                    Ok(true) => {
+                        info!("Linked {file_name} from store");
+                        if let Some(time) = options.mtime {
+                            mtime::apply(&dest, time);
+                        }
+                        continue;
+                    }
+                    Ok(false) => {}
+                    Err(e) => warn!("failed to link {file_name} from store: {e}"),
+                }
+            }
         }
 
         let file_url = format!("{file_server}/data{file_path}");
-        info!("Downloading {}", file_name);
+        info!("Queued {}", file_name);
 
         let api = api.clone();
         let save_dir = save_path.clone();
@@ -157,20 +395,45 @@ pub(super) async fn download_post_attachments(
             url,
             save_dir,
             file_name,
+            options: DownloadOptions {
+                filter: filter.clone(),
+                ..options.clone()
+            },
         };
-        let _ = tx.send(payload);
+        queue.push(payload).await;
     }
 
-    for position in 1..max_concurrency + 1 {
-        let rx = rx.clone();
-        tasks.spawn(worker(rx, position));
+    if let Some(time) = options.mtime {
+        mtime::apply(save_path, time);
     }
 
-    tasks.join_all().await;
+    Ok(())
+}
 
-    if DONE.load(Ordering::Relaxed) {
-        anyhow::bail!("Received SIGINT, exiting!");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(json: &str) -> Post {
+        kemono_api::serde_json::from_str(json).unwrap()
     }
 
-    Ok(())
+    #[test]
+    fn listing_entries_with_a_substring_are_fetched() {
+        let post =
+            entry(r#"{"id": "1", "title": "t", "substring": "<img src=\"/data/ab/cd/x.png\">"}"#);
+        assert!(!is_listing_complete(&post));
+    }
+
+    #[test]
+    fn inline_media_of_listed_posts_is_found() {
+        let post = entry(
+            r#"{"id": "1", "title": "t", "content": "<p><img src=\"/data/ab/cd/x.png\"></p>"}"#,
+        );
+        assert!(is_listing_complete(&post));
+        let post_info = from_listing(post);
+        let media = content::extract_media(&post_info.post.content, "https://kemono.su");
+        assert_eq!(media.len(), 1);
+        assert_eq!(media[0].path, "/ab/cd/x.png");
+    }
 }
@@ -1,6 +1,13 @@
+use std::borrow::Cow;
+
+use crate::filter::Filter;
+
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct Attachment<'a> {
     pub file_server: &'a str,
-    pub file_name: &'a str,
+    /// Local name, may differ from the server's after resolving collisions
+    pub file_name: Cow<'a, str>,
     pub file_path: &'a str,
+    /// What is left of the filter once the post and file name are known
+    pub filter: Filter,
 }
@@ -0,0 +1,93 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use tracing::{info, warn};
+
+use super::model::Attachment;
+use crate::verify::expected_hash;
+
+/// What to do with different files of a post that share a name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NameCollision {
+    /// image.png, image_1.png, image_2.png
+    #[default]
+    Index,
+    /// image.png, image_3fa9c2d1.png, using the file's SHA-256
+    Hash,
+    /// Keep only the last file with the name
+    Overwrite,
+}
+
+/// Names are compared case-insensitively, as on Windows and macOS
+fn key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+pub(super) fn split_ext(name: &str) -> (&str, &str) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, ext),
+        _ => (name, ""),
+    }
+}
+
+fn with_suffix(name: &str, suffix: &str) -> String {
+    match split_ext(name) {
+        (stem, "") => format!("{stem}_{suffix}"),
+        (stem, ext) => format!("{stem}_{suffix}.{ext}"),
+    }
+}
+
+/// Give every attachment a distinct file name, attachments must already be unique by path
+pub fn resolve_collisions(
+    attachments: Vec<Attachment<'_>>,
+    strategy: NameCollision,
+) -> Vec<Attachment<'_>> {
+    if strategy == NameCollision::Overwrite {
+        let last = attachments
+            .iter()
+            .enumerate()
+            .map(|(i, attach)| (key(&attach.file_name), i))
+            .collect::<HashMap<_, _>>();
+        return attachments
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, attach)| {
+                if last[&key(&attach.file_name)] == i {
+                    return Some(attach);
+                }
+                warn!("{} is overwritten by a later file", attach.file_path);
+                None
+            })
+            .collect();
+    }
+
+    let mut taken = HashSet::new();
+    attachments
+        .into_iter()
+        .map(|mut attach| {
+            if taken.insert(key(&attach.file_name)) {
+                return attach;
+            }
+            let hashed = match strategy {
+                NameCollision::Hash => expected_hash(attach.file_path)
+                    .map(|hash| with_suffix(&attach.file_name, &hash[..8]))
+                    .filter(|name| taken.insert(key(name))),
+                _ => None,
+            };
+            let name = hashed.unwrap_or_else(|| {
+                (1..)
+                    .map(|i| with_suffix(&attach.file_name, &i.to_string()))
+                    .find(|name| taken.insert(key(name)))
+                    .expect("unbounded range")
+            });
+            info!(
+                "renamed {} to {name} to avoid a name collision",
+                attach.file_name
+            );
+            attach.file_name = Cow::Owned(name);
+            attach
+        })
+        .collect()
+}
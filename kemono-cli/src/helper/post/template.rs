@@ -0,0 +1,235 @@
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use chrono::format::{Item, StrftimeItems};
+use kemono_api::{model::post_info::Post, Timestamp};
+
+use super::naming::split_ext;
+use crate::{
+    helper::utils::Author,
+    utils::{normalize_pathname, UnicodeForm},
+    verify::expected_hash,
+};
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Output path with `{placeholder}` or `{placeholder:format}` fields
+///
+/// Post fields: `service`, `user_id`, `author`, `public_id`, `id`, `title`,
+/// `published` and `edited` (strftime format, default `%Y-%m-%d`).
+/// File fields: `index` (format is the zero-padded width, e.g. `{index:03}`),
+/// `name`, `stem`, `ext` and `hash`. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Part>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Field, Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Service,
+    UserId,
+    Author,
+    PublicId,
+    Id,
+    Title,
+    Published,
+    Edited,
+    Index,
+    Name,
+    Stem,
+    Ext,
+    Hash,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "service" => Field::Service,
+            "user_id" => Field::UserId,
+            "author" => Field::Author,
+            "public_id" => Field::PublicId,
+            "id" => Field::Id,
+            "title" => Field::Title,
+            "published" => Field::Published,
+            "edited" => Field::Edited,
+            "index" => Field::Index,
+            "name" => Field::Name,
+            "stem" => Field::Stem,
+            "ext" => Field::Ext,
+            "hash" => Field::Hash,
+            _ => return None,
+        })
+    }
+
+    fn is_file_field(self) -> bool {
+        matches!(
+            self,
+            Field::Index | Field::Name | Field::Stem | Field::Ext | Field::Hash
+        )
+    }
+}
+
+fn check_format(field: Field, name: &str, format: &str) -> Result<()> {
+    match field {
+        Field::Published | Field::Edited => {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                anyhow::bail!("invalid date format in {{{name}:{format}}}");
+            }
+        }
+        Field::Index => {
+            format
+                .parse::<usize>()
+                .map_err(|_| anyhow!("{{index:{format}}} expects a width like 03"))?;
+        }
+        _ => anyhow::bail!("{{{name}}} takes no format"),
+    }
+    Ok(())
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' | '}' if chars.peek() == Some(&ch) => {
+                    chars.next();
+                    literal.push(ch);
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) => inner.push(ch),
+                            None => anyhow::bail!("unclosed {{ in template {s}"),
+                        }
+                    }
+                    let (name, format) = match inner.split_once(':') {
+                        Some((name, format)) => (name, Some(format)),
+                        None => (inner.as_str(), None),
+                    };
+                    let field = Field::parse(name)
+                        .ok_or_else(|| anyhow!("unknown placeholder {{{name}}}"))?;
+                    if let Some(format) = format {
+                        check_format(field, name, format)?;
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field, format.map(String::from)));
+                }
+                '}' => anyhow::bail!("unmatched }} in template {s}"),
+                ch => literal.push(ch),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template(parts))
+    }
+}
+
+/// Values available to a template
+pub struct Vars<'a> {
+    pub author: &'a Author,
+    pub post: &'a Post,
+    pub file: Option<FileVars<'a>>,
+}
+
+pub struct FileVars<'a> {
+    /// 1-based position in the post's own file order, counted before filtering
+    pub index: usize,
+    pub name: &'a str,
+    pub path: &'a str,
+}
+
+fn format_date(timestamp: Option<&Timestamp>, format: Option<&str>) -> String {
+    match timestamp.and_then(Timestamp::datetime) {
+        Some(datetime) => datetime
+            .format(format.unwrap_or(DEFAULT_DATE_FORMAT))
+            .to_string(),
+        None => String::new(),
+    }
+}
+
+/// Parse a post directory template, which has no file to take file fields from
+pub fn parse_path_template(s: &str) -> Result<Template> {
+    let template: Template = s.parse()?;
+    if template.uses_file_fields() {
+        anyhow::bail!("file placeholders cannot be used in a path template");
+    }
+    Ok(template)
+}
+
+impl Template {
+    fn uses_file_fields(&self) -> bool {
+        self.0
+            .iter()
+            .any(|part| matches!(part, Part::Field(field, _) if field.is_file_field()))
+    }
+
+    fn value(&self, field: Field, format: Option<&str>, vars: &Vars<'_>) -> String {
+        let Vars { author, post, file } = vars;
+        match field {
+            Field::Service => post.service.to_string(),
+            Field::UserId => post.user.to_string(),
+            Field::Author => author.name.clone(),
+            Field::PublicId => author.public_id.clone().unwrap_or_default(),
+            Field::Id => post.id.to_string(),
+            Field::Title => post.title.clone(),
+            Field::Published => format_date(post.published.as_ref(), format),
+            Field::Edited => format_date(post.edited.as_ref(), format),
+            _ => {
+                let Some(FileVars { index, name, path }) = file else {
+                    return String::new();
+                };
+                match field {
+                    Field::Index => {
+                        let width = format.and_then(|f| f.parse().ok()).unwrap_or(0);
+                        format!("{index:0width$}")
+                    }
+                    Field::Name => name.to_string(),
+                    Field::Stem => split_ext(name).0.to_string(),
+                    Field::Ext => split_ext(name).1.to_string(),
+                    _ => expected_hash(path).unwrap_or_default(),
+                }
+            }
+        }
+    }
+
+    /// Expand fields, each value sanitized so it cannot add path components
+    fn expand(&self, vars: &Vars<'_>, form: UnicodeForm) -> String {
+        let mut out = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Literal(literal) => out.push_str(literal),
+                Part::Field(field, format) => {
+                    let value = self.value(*field, format.as_deref(), vars);
+                    out.push_str(&normalize_pathname(&value, form));
+                }
+            }
+        }
+        out
+    }
+
+    /// Relative directory, `/` in the template separates components
+    pub fn render_path(&self, vars: &Vars<'_>, form: UnicodeForm) -> PathBuf {
+        self.expand(vars, form)
+            .split('/')
+            .map(|component| normalize_pathname(component, form))
+            .filter(|component| !component.is_empty() && component != "." && component != "..")
+            .collect()
+    }
+
+    pub fn render_file_name(&self, vars: &Vars<'_>, form: UnicodeForm) -> String {
+        normalize_pathname(&self.expand(vars, form), form)
+    }
+}
@@ -1,27 +1,94 @@
-use std::{path::PathBuf, sync::mpmc::Receiver};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use kemono_api::API;
-use tracing::error;
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinSet,
+};
+use tracing::{error, info};
 
-use crate::utils::download_file;
+use crate::utils::{download_file, DownloadOptions};
 
 pub struct Payload {
     pub api: API,
     pub url: String,
     pub save_dir: PathBuf,
     pub file_name: String,
+    pub options: DownloadOptions,
 }
 
-pub async fn worker(rx: Receiver<Payload>, position: u16) {
-    while let Ok(Payload {
-        api,
-        url,
-        save_dir,
-        file_name,
-    }) = rx.try_recv()
-    {
-        if let Err(e) = download_file(api, &url, &save_dir, &file_name, position).await {
+/// Destinations being downloaded, so payloads saving to the same path take turns
+///
+/// Posts can share a directory, e.g. with `--post-dir title`, and must not write the same
+/// `.incomplete` file at once.
+type InFlight = Arc<std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>>;
+
+/// File downloads of the whole run, shared by all posts
+///
+/// `max_concurrency` workers keep that many files in flight regardless of which post they belong to.
+pub struct Queue {
+    tx: mpsc::Sender<Payload>,
+    workers: JoinSet<()>,
+}
+
+impl Queue {
+    pub fn new(max_concurrency: usize) -> Self {
+        let max_concurrency = max_concurrency.max(1);
+        // small buffer so post metadata is not fetched far ahead of the downloads
+        let (tx, rx) = mpsc::channel(max_concurrency);
+        let rx = Arc::new(Mutex::new(rx));
+        let in_flight = InFlight::default();
+        let mut workers = JoinSet::new();
+        for position in 1..=max_concurrency as u16 {
+            workers.spawn(worker(rx.clone(), in_flight.clone(), position));
+        }
+        Queue { tx, workers }
+    }
+
+    /// Waits while the queue is full
+    pub async fn push(&self, payload: Payload) {
+        let _ = self.tx.send(payload).await;
+    }
+
+    /// Wait for every queued download to finish
+    pub async fn finish(self) {
+        drop(self.tx);
+        self.workers.join_all().await;
+    }
+}
+
+async fn worker(rx: Arc<Mutex<mpsc::Receiver<Payload>>>, in_flight: InFlight, position: u16) {
+    loop {
+        let Some(Payload {
+            api,
+            url,
+            save_dir,
+            file_name,
+            options,
+        }) = rx.lock().await.recv().await
+        else {
+            break;
+        };
+
+        let dest = save_dir.join(&file_name);
+        let lock = in_flight
+            .lock()
+            .unwrap()
+            .entry(dest.clone())
+            .or_default()
+            .clone();
+        let guard = lock.lock().await;
+
+        info!("Downloading {file_name}");
+        if let Err(e) = download_file(api, &url, &save_dir, &file_name, position, &options).await {
             error!("error downloading {file_name}: {e}");
         }
+
+        drop(guard);
+        let mut in_flight = in_flight.lock().unwrap();
+        // only the map and this worker still hold the lock, nobody waits for the path
+        if Arc::strong_count(&lock) == 2 {
+            in_flight.remove(&dest);
+        }
     }
 }
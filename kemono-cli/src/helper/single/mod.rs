@@ -1,32 +1,45 @@
+use std::sync::atomic::Ordering;
+
 use anyhow::Result;
-use kemono_api::{
-    model::post_info::{Post, PostInfo},
-    API,
-};
+use kemono_api::{Error, PostId};
+use tracing::info;
 
 use crate::helper::ctx::Context;
+use crate::DONE;
 
-use super::{post::download_post, utils::get_author_name};
+use super::{
+    post::{compile_filter, download_post_info, Queue},
+    utils::{get_author, new_api},
+};
 
-pub async fn download_one(ctx: impl Context<'_>, post_id: &str) -> Result<()> {
-    let web_name = ctx.web_name();
+pub async fn download_one(ctx: impl Context<'_>, post_id: &PostId) -> Result<()> {
+    let service = ctx.service();
     let user_id = ctx.user_id();
-    let base_url = ctx.api_base_url();
-
-    let api = API::try_with_base_url(base_url)?;
-
-    let author = get_author_name(&api, web_name, user_id).await?;
-    let PostInfo {
-        post: Post {
-            title: post_title, ..
-        },
-        ..
-    } = api.get_post_info(web_name, user_id, post_id).await?;
-    let mut post_title = post_title.as_str();
-    if post_title.is_empty() {
-        post_title = post_id;
+
+    let api = new_api(&ctx)?;
+
+    let author = get_author(&api, service, user_id).await?;
+    let filter = compile_filter(&ctx)?;
+    let post_info = match api.get_post_info(service, user_id, post_id).await {
+        Ok(info) => info,
+        Err(Error::NotFound { .. }) => {
+            anyhow::bail!("post {service}/{user_id}/{post_id} not found")
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if !ctx.date_range().contains(&post_info.post) {
+        info!("Skipped {} by date", post_info.post.title);
+        return Ok(());
     }
 
-    download_post(&ctx, &api, post_id, &post_title, &author).await?;
+    let queue = Queue::new(ctx.max_concurrency());
+    let result = download_post_info(&ctx, &api, &queue, post_info, &author, &filter).await;
+    queue.finish().await;
+    result?;
+
+    if DONE.load(Ordering::Relaxed) {
+        anyhow::bail!("Received SIGINT, exiting!");
+    }
     Ok(())
 }
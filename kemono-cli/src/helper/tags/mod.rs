@@ -0,0 +1,32 @@
+use anyhow::Result;
+use kemono_api::Error;
+
+use crate::helper::ctx::Context;
+
+use super::utils::new_api;
+
+/// Print the tags of the creator's posts, most used first
+pub async fn list_tags(ctx: impl Context<'_>) -> Result<()> {
+    let service = ctx.service();
+    let user_id = ctx.user_id();
+
+    let api = new_api(&ctx)?;
+    let mut tags = match api.get_creator_tags(service, user_id).await {
+        Ok(tags) => tags,
+        Err(Error::NotFound { .. }) => anyhow::bail!("creator {service}/{user_id} not found"),
+        Err(e) => return Err(e.into()),
+    };
+    tags.sort_by(|a, b| {
+        b.post_count
+            .cmp(&a.post_count)
+            .then_with(|| a.tag.cmp(&b.tag))
+    });
+
+    let width = tags
+        .first()
+        .map_or(0, |tag| tag.post_count.to_string().len());
+    for tag in &tags {
+        println!("{:>width$}  {}", tag.post_count, tag.tag);
+    }
+    Ok(())
+}
@@ -1,15 +1,82 @@
 use anyhow::{anyhow, Result};
 use tracing::info;
 
-use kemono_api::{model::user_profile::UserProfile, API};
+use kemono_api::{
+    model::user_profile::UserProfile, CancelFlag, CreatorId, RetryPolicy, Service, API,
+};
 
-pub async fn get_author_name(api: &API, web_name: &str, user_id: &str) -> Result<String> {
+use crate::{
+    helper::ctx::Context,
+    utils::{legacy_pathname, normalize_pathname, UnicodeForm},
+    DONE,
+};
+
+/// Creator of the downloaded posts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Author {
+    pub name: String,
+    pub public_id: Option<String>,
+}
+
+impl Author {
+    /// Directory of the creator below the output dir
+    pub fn dir_name(&self, form: UnicodeForm) -> String {
+        normalize_pathname(self.public_id.as_deref().unwrap_or(&self.name), form)
+    }
+
+    /// Directory of the creator as created by older versions for creator downloads
+    pub fn legacy_dir_name(&self) -> String {
+        legacy_pathname(self.public_id.as_deref().unwrap_or(&self.name))
+    }
+}
+
+/// Build the API client shared by all downloads of this run
+pub fn new_api<'a>(ctx: &impl Context<'a>) -> Result<API> {
+    let retry = RetryPolicy {
+        max_attempts: ctx.max_retries() + 1,
+        base_delay: ctx.retry_delay(),
+        cancel: Some(CancelFlag::new(&DONE)),
+        ..Default::default()
+    };
+    let mut builder = API::builder()
+        .base_url(ctx.api_base_url())
+        .retry_policy(retry);
+
+    if let Some(proxy) = ctx.proxy() {
+        builder = builder.proxy(proxy);
+    }
+    if let Some(user_agent) = ctx.user_agent() {
+        builder = builder.user_agent(user_agent);
+    }
+    for header in ctx.headers() {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| anyhow!("header should be in `Name: Value` form: {header}"))?;
+        builder = builder.header(name.trim(), value.trim());
+    }
+    if let Some(timeout) = ctx.connect_timeout() {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = ctx.read_timeout() {
+        builder = builder.read_timeout(timeout);
+    }
+    if let Some(addr) = ctx.bind_address() {
+        builder = builder.local_address(addr);
+    }
+    if let Some(version) = ctx.ip_version() {
+        builder = builder.ip_version(version);
+    }
+
+    Ok(builder.build()?)
+}
+
+pub async fn get_author(api: &API, service: &Service, user_id: &CreatorId) -> Result<Author> {
     let UserProfile {
         ref public_id,
         ref name,
         ..
     } = api
-        .get_user_profile(web_name, user_id)
+        .get_user_profile(service, user_id)
         .await
         .map_err(|e| anyhow!("failed to get user profile: {e}"))?;
 
@@ -17,5 +84,8 @@ pub async fn get_author_name(api: &API, web_name: &str, user_id: &str) -> Result
         info!("user ({user_id}): {public_id}");
     }
 
-    Ok(public_id.as_deref().unwrap_or(name).into())
+    Ok(Author {
+        name: name.clone(),
+        public_id: public_id.clone(),
+    })
 }
@@ -1,9 +1,15 @@
-#![feature(mpmc_channel)]
-
 use std::sync::atomic::AtomicBool;
 
+pub mod category;
+pub mod dates;
+pub mod filter;
 pub mod helper;
+pub mod mtime;
+mod segment;
+pub mod store;
+pub mod throttle;
 pub mod utils;
+pub mod verify;
 
 pub static DONE: AtomicBool = AtomicBool::new(false);
 
@@ -1,26 +1,49 @@
 use std::{
     fs,
     io::IsTerminal,
+    net::IpAddr,
     path::PathBuf,
     sync::{atomic::Ordering, LazyLock, Mutex},
+    time::Duration,
 };
 
 use anyhow::Result;
-use clap::Parser;
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
 use kdam::tqdm;
 use tracing::{error, info, level_filters::LevelFilter};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
+use kemono_api::IpVersion;
 use kemono_cli::{
-    helper::{batch::download_all, ctx::Args, single::download_one},
+    category::Category,
+    dates::{parse_date, DateField, DateRange},
+    filter::Filter,
+    helper::{
+        batch::download_all,
+        ctx::Args,
+        post::{parse_path_template, ExportFormat, NameCollision, PostDirStyle, Template},
+        single::download_one,
+        tags::list_tags,
+    },
+    mtime::MtimeSource,
     stdio::WriteBar,
-    utils::{extract_info, DownloadInfo},
-    DONE,
+    throttle::{self, Window},
+    utils::{extract_info, parse_seconds, parse_size, DownloadInfo, UnicodeForm},
+    verify, DONE,
 };
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "Download tool")]
+#[command(
+    author,
+    version,
+    about = "Download tool",
+    subcommand_negates_reqs = true
+)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// kemono URL to fetch posts, can be user profile or single post
     ///
     /// Example:
@@ -28,7 +51,8 @@ struct Cli {
     /// https://kemono.su/fanbox/user/4107959
     ///
     /// https://kemono.su/fanbox/user/4107959/post/7999699
-    url: String,
+    #[arg(required = true)]
+    url: Option<String>,
 
     /// Output directory of fetched posts
     #[arg(long, default_value = "./download")]
@@ -62,9 +86,222 @@ struct Cli {
     #[arg(long, short = 'B')]
     blacklist_filename_regex: Vec<String>,
 
+    /// Only download posts with this tag
+    ///
+    /// Specify multiple times means 'AND' semantic
+    #[arg(long)]
+    tag: Vec<String>,
+
+    /// Skip posts with this tag
+    ///
+    /// Can be specified multiple times
+    #[arg(long)]
+    exclude_tag: Vec<String>,
+
+    /// Only download files of these kinds, told apart by extension or else by MIME type
+    ///
+    /// Example: --only images,videos
+    #[arg(long, value_enum, value_delimiter = ',')]
+    only: Vec<Category>,
+
+    /// Skip files smaller than this, checked before downloading
+    ///
+    /// Example: 100K, 1.5M
+    #[arg(long, value_parser = parse_size)]
+    min_size: Option<u64>,
+
+    /// Skip files larger than this, checked before downloading
+    ///
+    /// Example: 500M, 2G
+    #[arg(long, value_parser = parse_size)]
+    max_size: Option<u64>,
+
     /// Switch to coomer.su endpoint
     #[arg(long, default_value_t = false)]
     coomer: bool,
+
+    /// Maximum number of retries for a failed request or interrupted download
+    #[arg(long, default_value_t = 4)]
+    max_retries: u32,
+
+    /// Initial delay in seconds between retries, doubled on every further attempt
+    ///
+    /// A `Retry-After` header sent by the server takes precedence
+    #[arg(long, default_value = "1", value_parser = parse_seconds)]
+    retry_delay: Duration,
+
+    /// Proxy for all requests
+    ///
+    /// Example: socks5://127.0.0.1:1080, http://proxy.example.com:8080
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Override the User-Agent sent with every request
+    #[arg(long)]
+    user_agent: Option<String>,
+
+    /// Extra request header in `Name: Value` form
+    ///
+    /// Can be specified multiple times
+    #[arg(long, short = 'H')]
+    header: Vec<String>,
+
+    /// Timeout in seconds for establishing a connection
+    #[arg(long, value_parser = parse_seconds)]
+    connect_timeout: Option<Duration>,
+
+    /// Timeout in seconds between two reads of a response
+    #[arg(long, value_parser = parse_seconds)]
+    read_timeout: Option<Duration>,
+
+    /// Local address to bind outgoing connections to
+    #[arg(long)]
+    bind_address: Option<IpAddr>,
+
+    /// Only connect over IPv4
+    #[arg(long, short = '4', conflicts_with = "ipv6")]
+    ipv4: bool,
+
+    /// Only connect over IPv6
+    #[arg(long, short = '6')]
+    ipv6: bool,
+
+    /// Also save each post as a standalone page linking to the downloaded files
+    #[arg(long, value_enum)]
+    export: Option<ExportFormat>,
+
+    /// Number of parallel connections used for a single large file
+    ///
+    /// Requires server support for range requests, 1 disables segmented downloads
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..=32))]
+    segments: u64,
+
+    /// Minimum file size for segmented downloads
+    ///
+    /// Example: 64M, 1.5G
+    #[arg(long, default_value = "64M", value_parser = parse_size)]
+    segment_threshold: u64,
+
+    /// Skip checking files against the SHA-256 in their server path
+    #[arg(long)]
+    no_verify: bool,
+
+    /// Keep each file once in <OUTPUT_DIR>/.store and link it into every post using it
+    ///
+    /// Uses hardlinks, falling back to reflinks and then symlinks
+    #[arg(long)]
+    store: bool,
+
+    /// Limit the combined download speed, in bytes per second
+    ///
+    /// Example: 500K, 5M
+    #[arg(long, value_parser = parse_size)]
+    limit_rate: Option<u64>,
+
+    /// Use a different speed limit during a daily time window, in local time
+    ///
+    /// Format: HH:MM-HH:MM=RATE, where RATE is a size like 1M or `unlimited`.
+    /// Outside of all windows `--limit-rate` applies. Can be specified multiple times.
+    ///
+    /// Example: --limit-rate 1M --schedule 01:00-07:00=unlimited
+    #[arg(long)]
+    schedule: Vec<Window>,
+
+    /// Set the modification time of post directories and files from the post date
+    #[arg(long, value_enum)]
+    mtime: Option<MtimeSource>,
+
+    /// How to save different files of a post that have the same name
+    #[arg(long, value_enum, default_value_t = NameCollision::Index)]
+    on_name_collision: NameCollision,
+
+    /// How post directories are named
+    ///
+    /// Older versions used the title only, which merges posts with the same title
+    #[arg(long, value_enum, default_value_t = PostDirStyle::IdSuffix)]
+    post_dir: PostDirStyle,
+
+    /// Rename post directories created by older versions to the current naming
+    ///
+    /// Only directories whose metadata.json belongs to the post are renamed
+    #[arg(long)]
+    migrate_legacy_dirs: bool,
+
+    /// Directory of each post relative to the output dir, overrides `--post-dir`
+    ///
+    /// Placeholders: {service}, {user_id}, {author}, {public_id}, {id}, {title},
+    /// {published} and {edited} with an optional strftime format like {published:%Y-%m}.
+    /// `/` separates directories.
+    ///
+    /// Example: "{service}/{author}/{published} {title} [{id}]"
+    #[arg(long, value_parser = parse_path_template)]
+    path_template: Option<Template>,
+
+    /// File name of each downloaded file
+    ///
+    /// Placeholders: those of `--path-template`, plus {index} with an optional
+    /// zero-padded width like {index:03}, {name}, {stem}, {ext} and {hash}.
+    ///
+    /// Example: "{index:03}_{name}"
+    #[arg(long)]
+    filename_template: Option<Template>,
+
+    /// Unicode normalization of directory and file names
+    ///
+    /// Keeps names from the server consistent when the library is synced across systems
+    #[arg(long, value_enum, default_value_t = UnicodeForm::Nfc)]
+    unicode_form: UnicodeForm,
+
+    /// Only download posts and files matching this expression
+    ///
+    /// Fields: title, content, tag, service, id, published, added, edited, name, ext, size.
+    /// Operators: == and != (case-insensitive), ~ and !~ (regex), in [a, b],
+    /// and <, <=, >, >= for dates and sizes. Combine with and, or, not and parentheses.
+    ///
+    /// Example: 'title ~ "(?i)melody" and (ext in [mp4, m4v] or size > 100MB) and not tag == "wip"'
+    #[arg(long)]
+    filter: Option<Filter>,
+
+    /// Only download posts from this date on
+    ///
+    /// Either a date like 2024-01-31 or a time before now like 12h, 30d, 2w, 6m or 1y
+    #[arg(long, value_parser = parse_date)]
+    since: Option<DateTime<Utc>>,
+
+    /// Only download posts from before this date, in the format of `--since`
+    #[arg(long, value_parser = parse_date)]
+    until: Option<DateTime<Utc>>,
+
+    /// Post date `--since` and `--until` are compared with
+    #[arg(long, value_enum, default_value_t = DateField::Published)]
+    date_field: DateField,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the tags of a creator's posts with their post counts
+    Tags {
+        /// kemono URL of the creator
+        url: String,
+    },
+}
+
+/// Keep only the name of a `Name: Value` header for logging, values often carry cookies or tokens
+fn redact_header(header: &str) -> String {
+    match header.split_once(':') {
+        Some((name, _)) => format!("{name}: <redacted>"),
+        None => String::from("<redacted>"),
+    }
+}
+
+/// Hide the `user:pass@` part of a proxy URL for logging
+fn redact_proxy(proxy: &str) -> String {
+    let start = proxy.find("://").map_or(0, |i| i + 3);
+    let end = proxy[start..].find('/').map_or(proxy.len(), |i| start + i);
+    match proxy[start..end].rfind('@') {
+        Some(at) => format!("{}<redacted>{}", &proxy[..start], &proxy[start + at..]),
+        None => proxy.to_string(),
+    }
 }
 
 #[tokio::main]
@@ -77,8 +314,13 @@ async fn main() -> Result<()> {
             tracing_subscriber::fmt::layer()
                 .with_level(true)
                 .with_writer(|| {
-                    static PB: LazyLock<WriteBar> =
-                        LazyLock::new(|| WriteBar(Mutex::new(tqdm!(desc = "spent", position = 0, bar_format = "{desc}{elapsed}"))));
+                    static PB: LazyLock<WriteBar> = LazyLock::new(|| {
+                        WriteBar(Mutex::new(tqdm!(
+                            desc = "spent",
+                            position = 0,
+                            bar_format = "{desc}{elapsed}"
+                        )))
+                    });
                     &*PB
                 })
                 .with_filter(
@@ -89,9 +331,14 @@ async fn main() -> Result<()> {
         )
         .init();
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    let header = std::mem::take(&mut cli.header);
+    cli.header = header.iter().map(|h| redact_header(h)).collect();
+    let proxy = cli.proxy.take();
+    cli.proxy = proxy.as_deref().map(redact_proxy);
     info!("Started with arguments: {cli:?}");
     let Cli {
+        command,
         url,
         output_dir,
         max_concurrency,
@@ -99,8 +346,85 @@ async fn main() -> Result<()> {
         blacklist_regex,
         whitelist_filename_regex,
         blacklist_filename_regex,
+        tag,
+        exclude_tag,
+        only,
+        min_size,
+        max_size,
         coomer,
-    } = Cli::parse();
+        max_retries,
+        retry_delay,
+        proxy: _,
+        user_agent,
+        header: _,
+        connect_timeout,
+        read_timeout,
+        bind_address,
+        ipv4,
+        ipv6,
+        export,
+        segments,
+        segment_threshold,
+        no_verify,
+        store,
+        limit_rate,
+        schedule,
+        mtime,
+        on_name_collision,
+        post_dir,
+        migrate_legacy_dirs,
+        path_template,
+        filename_template,
+        unicode_form,
+        filter,
+        since,
+        until,
+        date_field,
+    } = cli;
+
+    let url = match &command {
+        Some(Command::Tags { url }) => url.clone(),
+        None => url.expect("url is required without a subcommand"),
+    };
+    let DownloadInfo {
+        service,
+        user_id,
+        post_id,
+    } = extract_info(&url)?;
+
+    let mut args = Args::builder();
+    args.service(service)
+        .user_id(user_id)
+        .api_base_url(
+            if coomer {
+                "https://coomer.su"
+            } else {
+                "https://kemono.su"
+            }
+            .into(),
+        )
+        .max_retries(max_retries)
+        .retry_delay(retry_delay)
+        .proxy(proxy)
+        .user_agent(user_agent)
+        .headers(header)
+        .connect_timeout(connect_timeout)
+        .read_timeout(read_timeout)
+        .bind_address(bind_address)
+        .ip_version(match (ipv4, ipv6) {
+            (true, _) => Some(IpVersion::V4),
+            (_, true) => Some(IpVersion::V6),
+            _ => None,
+        });
+
+    if let Some(Command::Tags { .. }) = command {
+        // only the connection settings apply to listing tags
+        if let Err(e) = list_tags(&args.build()?).await {
+            error!("{e}");
+        }
+        kdam::term::show_cursor()?;
+        return Ok(());
+    }
 
     info!("Download URL: {}", &url);
 
@@ -117,29 +441,38 @@ async fn main() -> Result<()> {
         DONE.store(true, Ordering::Release);
     })?;
 
-    let DownloadInfo {
-        web_name,
-        user_id,
-        post_id,
-    } = extract_info(&url)?;
+    throttle::init(limit_rate, schedule);
 
-    let args = Args::builder()
-        .web_name(web_name)
-        .user_id(user_id)
+    let args = args
         .max_concurrency(max_concurrency)
         .output_dir(output_dir)
         .whitelist_regexes(whitelist_regex)
         .blacklist_regexes(blacklist_regex)
         .whitelist_filename_regexes(whitelist_filename_regex)
         .blacklist_filename_regexes(blacklist_filename_regex)
-        .api_base_url(
-            if coomer {
-                "https://coomer.su"
-            } else {
-                "https://kemono.su"
-            }
-            .into(),
-        )
+        .tags(tag)
+        .excluded_tags(exclude_tag)
+        .only(only)
+        .min_size(min_size)
+        .max_size(max_size)
+        .export_format(export)
+        .segments(segments)
+        .segment_threshold(segment_threshold)
+        .verify(!no_verify)
+        .use_store(store)
+        .mtime_source(mtime)
+        .name_collision(on_name_collision)
+        .post_dir_style(post_dir)
+        .migrate_legacy_dirs(migrate_legacy_dirs)
+        .path_template(path_template)
+        .filename_template(filename_template)
+        .unicode_form(unicode_form)
+        .filter(filter)
+        .date_range(DateRange {
+            field: date_field,
+            since,
+            until,
+        })
         .build()?;
 
     match post_id {
@@ -155,6 +488,8 @@ async fn main() -> Result<()> {
         }
     }
 
+    verify::report();
+
     kdam::term::show_cursor()?;
     info!("Task Exit");
 
@@ -0,0 +1,34 @@
+//! Modification times of downloaded files taken from the post dates
+
+use std::{path::Path, time::SystemTime};
+
+use filetime::FileTime;
+use kemono_api::model::post_info::Post;
+use tracing::warn;
+
+/// Post date used as mtime of its directory and files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MtimeSource {
+    Published,
+    /// Falls back to the published date for posts never edited
+    Edited,
+}
+
+impl MtimeSource {
+    pub fn time(self, post: &Post) -> Option<SystemTime> {
+        let timestamp = match self {
+            MtimeSource::Published => post.published.as_ref(),
+            MtimeSource::Edited => post.edited.as_ref().or(post.published.as_ref()),
+        };
+        timestamp?.datetime().map(SystemTime::from)
+    }
+}
+
+/// Set the mtime of `path`, failures are only logged
+///
+/// Files shared through the store are hardlinks, so they keep the mtime of the post set last.
+pub(crate) fn apply(path: &Path, time: SystemTime) {
+    if let Err(e) = filetime::set_file_mtime(path, FileTime::from_system_time(time)) {
+        warn!("failed to set mtime of {}: {e}", path.display());
+    }
+}
@@ -0,0 +1,273 @@
+//! Parallel download of one file as several byte ranges
+
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+};
+
+use anyhow::{anyhow, Result};
+use futures_lite::StreamExt;
+use kdam::{BarExt, RichProgress};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncSeekExt, AsyncWriteExt, BufWriter},
+    sync::Mutex,
+    task::JoinSet,
+    time::timeout,
+};
+use tracing::{debug, warn};
+
+use kemono_api::{reqwest::StatusCode, ContentRange, API};
+
+use crate::{throttle, utils::STREAM_TIMEOUT, DONE};
+
+/// Persist segment progress after this many bytes
+const CHECKPOINT_BYTES: u64 = 8 * 1024 * 1024;
+/// Never split a file into segments smaller than this
+const MIN_SEGMENT_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    start: u64,
+    /// Inclusive
+    end: u64,
+    done: u64,
+}
+
+impl Segment {
+    fn pos(&self) -> u64 {
+        self.start + self.done
+    }
+
+    fn remaining(&self) -> u64 {
+        (self.end + 1).saturating_sub(self.pos())
+    }
+}
+
+/// Resume state of a segmented download, kept next to the `.incomplete` file
+///
+/// Format: total size on the first line, then `start end done` per segment.
+#[derive(Debug)]
+struct State {
+    path: PathBuf,
+    total: u64,
+    segments: Vec<Segment>,
+}
+
+impl State {
+    fn new(path: PathBuf, total: u64, count: u64) -> Self {
+        let count = count.min(total / MIN_SEGMENT_SIZE).max(1);
+        let size = total.div_ceil(count);
+        let segments = (0..count)
+            .map(|i| Segment {
+                start: i * size,
+                end: ((i + 1) * size).min(total) - 1,
+                done: 0,
+            })
+            .collect();
+        State {
+            path,
+            total,
+            segments,
+        }
+    }
+
+    /// `None` if there is no state or it belongs to a different file size
+    async fn load(path: PathBuf, total: u64) -> Option<Self> {
+        let text = fs::read_to_string(&path).await.ok()?;
+        let mut lines = text.lines();
+        if lines.next()?.parse::<u64>().ok()? != total {
+            return None;
+        }
+        let segments = lines
+            .map(|line| {
+                let mut fields = line.split_whitespace().map(str::parse::<u64>);
+                Some(Segment {
+                    start: fields.next()?.ok()?,
+                    end: fields.next()?.ok()?,
+                    done: fields.next()?.ok()?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(State {
+            path,
+            total,
+            segments,
+        })
+    }
+
+    async fn save(&self) -> Result<()> {
+        let mut text = format!("{}\n", self.total);
+        for Segment { start, end, done } in &self.segments {
+            text += &format!("{start} {end} {done}\n");
+        }
+        let tmp_path = self.path.with_extension("segments.tmp");
+        fs::write(&tmp_path, text).await?;
+        fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+
+    fn downloaded(&self) -> u64 {
+        self.segments.iter().map(|s| s.done).sum()
+    }
+}
+
+pub(crate) fn state_path(partial_file_path: &Path) -> PathBuf {
+    PathBuf::from(partial_file_path.to_string_lossy().into_owned() + ".segments")
+}
+
+/// Bytes already downloaded by a previous, interrupted run
+pub(crate) async fn resumed_bytes(partial_file_path: &Path, total_size: u64) -> u64 {
+    State::load(state_path(partial_file_path), total_size)
+        .await
+        .map(|state| state.downloaded())
+        .unwrap_or(0)
+}
+
+/// Download `url` into `partial_file_path` over up to `count` connections
+///
+/// Returns `false` if the download was interrupted, in which case the next call resumes it.
+pub(crate) async fn download(
+    api: &API,
+    url: &str,
+    partial_file_path: &Path,
+    total_size: u64,
+    count: u64,
+    pb: RichProgress,
+) -> Result<bool> {
+    let path = state_path(partial_file_path);
+    let state = match State::load(path.clone(), total_size).await {
+        Some(state) if partial_file_path.exists() => state,
+        _ => {
+            let file = File::create(partial_file_path).await?;
+            file.set_len(total_size).await?;
+            let state = State::new(path, total_size, count);
+            state.save().await?;
+            state
+        }
+    };
+    debug!("segments: {:?}", state.segments);
+
+    let pending = (0..state.segments.len())
+        .filter(|&i| state.segments[i].remaining() > 0)
+        .collect::<Vec<_>>();
+    let state = Arc::new(Mutex::new(state));
+    let pb = Arc::new(std::sync::Mutex::new(pb));
+
+    let mut tasks = JoinSet::new();
+    for index in pending {
+        tasks.spawn(fetch_segment(
+            api.clone(),
+            url.to_string(),
+            partial_file_path.to_path_buf(),
+            index,
+            state.clone(),
+            pb.clone(),
+        ));
+    }
+
+    tasks
+        .join_all()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+    let state = state.lock().await;
+    if state.segments.iter().any(|s| s.remaining() > 0) {
+        return Ok(false);
+    }
+    fs::remove_file(&state.path).await?;
+    Ok(true)
+}
+
+async fn checkpoint(state: &Mutex<State>, index: usize, segment: Segment) -> Result<()> {
+    let mut state = state.lock().await;
+    state.segments[index] = segment;
+    state.save().await
+}
+
+async fn fetch_segment(
+    api: API,
+    url: String,
+    partial_file_path: PathBuf,
+    index: usize,
+    state: Arc<Mutex<State>>,
+    pb: Arc<std::sync::Mutex<RichProgress>>,
+) -> Result<()> {
+    let retry = api.retry_policy().clone();
+    let mut segment = state.lock().await.segments[index];
+    let mut file = File::options().write(true).open(&partial_file_path).await?;
+    let mut attempt = 0;
+
+    while segment.remaining() > 0 {
+        attempt += 1;
+        let resumed_at = segment.pos();
+
+        let resp = api.get_range(&url, segment.pos(), segment.end).await?;
+        if resp.status() != StatusCode::PARTIAL_CONTENT {
+            anyhow::bail!("server ignored range request, status {}", resp.status());
+        }
+        if ContentRange::from_response(&resp).is_none_or(|range| range.start != segment.pos()) {
+            anyhow::bail!("server sent a different range than requested");
+        }
+
+        file.seek(SeekFrom::Start(segment.pos())).await?;
+        let mut writer = BufWriter::new(&mut file);
+        let mut stream = resp.bytes_stream();
+        let mut unsaved = 0;
+
+        let interrupted = loop {
+            if DONE.load(Ordering::Relaxed) {
+                break None;
+            }
+            let data = match timeout(STREAM_TIMEOUT, stream.next()).await {
+                Ok(Some(Ok(data))) => data,
+                Ok(Some(Err(e))) => break Some(anyhow!(e)),
+                Ok(None) if segment.remaining() > 0 => {
+                    break Some(anyhow!("connection closed early"))
+                }
+                Ok(None) => break None,
+                Err(_) => break Some(anyhow!("no data received in {STREAM_TIMEOUT:?}")),
+            };
+
+            let len = (data.len() as u64).min(segment.remaining());
+            throttle::acquire(len as usize).await;
+            writer.write_all(&data[..len as usize]).await?;
+            segment.done += len;
+            unsaved += len;
+            pb.lock().unwrap().update(len as usize)?;
+
+            if unsaved >= CHECKPOINT_BYTES {
+                writer.flush().await?;
+                checkpoint(&state, index, segment).await?;
+                unsaved = 0;
+            }
+            if segment.remaining() == 0 {
+                break None;
+            }
+        };
+        writer.flush().await?;
+        checkpoint(&state, index, segment).await?;
+
+        let Some(e) = interrupted else {
+            break;
+        };
+        if segment.pos() > resumed_at {
+            attempt = 1;
+        }
+        if attempt >= retry.max_attempts {
+            return Err(e.context(format!("segment {index} interrupted at {}", segment.pos())));
+        }
+        let delay = retry.backoff(attempt);
+        warn!(
+            "segment {index} interrupted at {}: {e}, resuming in {delay:?}",
+            segment.pos()
+        );
+        if !retry.sleep(delay).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! Content-addressed store shared by all posts and creators
+//!
+//! Files live at `<output>/.store/ab/cd/<sha256>` and are linked into post directories,
+//! so a file attached to many posts is downloaded and stored once.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use tokio::fs;
+use tracing::{debug, trace};
+
+const STORE_DIR: &str = ".store";
+
+#[derive(Debug, Clone)]
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Store {
+    pub fn new(output_dir: &Path) -> Self {
+        Store {
+            root: output_dir.join(STORE_DIR),
+        }
+    }
+
+    /// `hash` must be lowercase hex, as returned by [`crate::verify::expected_hash`]
+    fn entry(&self, hash: &str) -> PathBuf {
+        self.root.join(&hash[..2]).join(&hash[2..4]).join(hash)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.entry(hash).is_file()
+    }
+
+    /// Size of the stored file, `None` if it is not in the store
+    pub fn size(&self, hash: &str) -> Option<u64> {
+        std::fs::metadata(self.entry(hash))
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+    }
+
+    /// Link the stored file into `dest`, returns `false` if it is not in the store
+    pub async fn link_into(&self, hash: &str, dest: &Path) -> Result<bool> {
+        let entry = self.entry(hash);
+        if !entry.is_file() {
+            return Ok(false);
+        }
+        if is_same_file(&entry, dest) {
+            trace!("{} already linked", dest.display());
+            return Ok(true);
+        }
+
+        // link next to the destination first, so an existing copy is replaced atomically
+        let tmp_path = PathBuf::from(dest.to_string_lossy().into_owned() + ".linking");
+        let _ = fs::remove_file(&tmp_path).await;
+        let entry = fs::canonicalize(&entry).await?;
+        let method = link(&entry, &tmp_path).await?;
+        fs::rename(&tmp_path, dest).await?;
+        debug!("{method} {} from store", dest.display());
+        Ok(true)
+    }
+
+    /// Add a downloaded file to the store, sharing its data where the filesystem allows
+    pub async fn insert(&self, hash: &str, src: &Path) -> Result<()> {
+        let entry = self.entry(hash);
+        if entry.is_file() {
+            return Ok(());
+        }
+        if let Some(dir) = entry.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        let tmp_path = entry.with_extension("tmp");
+        let _ = fs::remove_file(&tmp_path).await;
+        if fs::hard_link(src, &tmp_path).await.is_err() {
+            let (src, dst) = (src.to_path_buf(), tmp_path.clone());
+            tokio::task::spawn_blocking(move || reflink_copy::reflink_or_copy(src, dst)).await??;
+        }
+        fs::rename(&tmp_path, &entry).await?;
+        trace!("stored {}", src.display());
+        Ok(())
+    }
+}
+
+/// Hardlink, else reflink, else symlink `src` to `dst`
+async fn link(src: &Path, dst: &Path) -> io::Result<&'static str> {
+    if fs::hard_link(src, dst).await.is_ok() {
+        return Ok("hardlinked");
+    }
+    let (from, to) = (src.to_path_buf(), dst.to_path_buf());
+    if tokio::task::spawn_blocking(move || reflink_copy::reflink(from, to))
+        .await
+        .is_ok_and(|result| result.is_ok())
+    {
+        return Ok("reflinked");
+    }
+    symlink(src, dst).await?;
+    Ok("symlinked")
+}
+
+#[cfg(unix)]
+async fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::symlink(src, dst).await
+}
+
+#[cfg(windows)]
+async fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::symlink_file(src, dst).await
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
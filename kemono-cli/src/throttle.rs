@@ -0,0 +1,131 @@
+//! Bandwidth limit shared by all downloads of the process
+
+use std::{
+    str::FromStr,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{Local, NaiveTime};
+
+use crate::utils::parse_size;
+
+static LIMITER: OnceLock<Limiter> = OnceLock::new();
+
+/// A daily time window with its own rate, `HH:MM-HH:MM=RATE`
+///
+/// `RATE` is a size per second like `1M`, or `unlimited`. Windows may wrap past midnight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+    rate: Option<u64>,
+}
+
+impl Window {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (range, rate) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected HH:MM-HH:MM=RATE, got {s}"))?;
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| anyhow!("expected HH:MM-HH:MM, got {range}"))?;
+        let rate = match rate.trim() {
+            "unlimited" | "0" => None,
+            rate => Some(parse_size(rate)?),
+        };
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M")?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M")?;
+        if start == end {
+            anyhow::bail!(
+                "window {range} is empty, use --limit-rate for a rate that applies all day"
+            );
+        }
+        Ok(Window { start, end, rate })
+    }
+}
+
+#[derive(Debug)]
+struct Limiter {
+    /// Bytes per second outside of all windows, `None` for unlimited
+    default: Option<u64>,
+    schedule: Vec<Window>,
+    bucket: Mutex<Bucket>,
+}
+
+/// Token bucket holding at most one second worth of bytes
+///
+/// Tokens may go negative, the caller that overdraws waits until the debt is paid off.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn take(&mut self, n: u64, rate: u64) -> Duration {
+        let now = Instant::now();
+        let rate = rate as f64;
+        let refill = now.duration_since(self.last).as_secs_f64() * rate;
+        self.tokens = (self.tokens + refill).min(rate);
+        self.last = now;
+        self.tokens -= n as f64;
+        match self.tokens < 0.0 {
+            true => Duration::from_secs_f64(-self.tokens / rate),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+impl Limiter {
+    /// The schedule is looked up on every call, so changes apply while downloading
+    fn current_rate(&self) -> Option<u64> {
+        let now = Local::now().time();
+        match self.schedule.iter().find(|window| window.contains(now)) {
+            Some(window) => window.rate,
+            None => self.default,
+        }
+    }
+}
+
+/// Set up the process-wide limit, without a rate or schedule downloads are not throttled
+pub fn init(rate: Option<u64>, schedule: Vec<Window>) {
+    if rate.is_none() && schedule.is_empty() {
+        return;
+    }
+    let _ = LIMITER.set(Limiter {
+        default: rate,
+        schedule,
+        bucket: Mutex::new(Bucket {
+            tokens: 0.0,
+            last: Instant::now(),
+        }),
+    });
+}
+
+/// Wait until `n` more bytes may be read under the current limit
+pub async fn acquire(n: usize) {
+    let Some(limiter) = LIMITER.get() else {
+        return;
+    };
+    let Some(rate) = limiter.current_rate().filter(|&rate| rate > 0) else {
+        return;
+    };
+    let wait = limiter.bucket.lock().unwrap().take(n as u64, rate);
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
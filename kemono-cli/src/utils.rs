@@ -1,45 +1,85 @@
 use std::{
     path::{Path, PathBuf},
     sync::atomic::Ordering,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use anyhow::{anyhow, Result};
 use futures_lite::StreamExt;
 use kdam::{tqdm, BarExt, Column, RichProgress, Spinner};
 use regex::RegexSet;
+use sha2::{Digest, Sha256};
 use tokio::{
     fs::{self, File},
     io::{AsyncWriteExt, BufWriter},
     time::timeout,
 };
-use tracing::{trace, warn};
+use tracing::{debug, info, trace, warn};
+use unicode_normalization::UnicodeNormalization;
 
 use kemono_api::{
     reqwest::{self, Url},
-    API,
+    ContentRange, CreatorId, Error, PostId, Service, API,
 };
 
-use crate::DONE;
+use crate::{
+    filter::{Attrs, Filter},
+    mtime, segment,
+    store::Store,
+    throttle, verify, DONE,
+};
+
+/// Give up on a stream that delivers no data for this long
+pub(crate) const STREAM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Per-file download settings shared by all workers
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Number of parallel connections for large files, 1 disables segmenting
+    pub segments: u64,
+    /// Files smaller than this are always fetched over one connection
+    pub segment_threshold: u64,
+    /// Check files against the SHA-256 in their data path
+    pub verify: bool,
+    /// Shared content-addressed store finished files are added to
+    pub store: Option<Store>,
+    /// Modification time for the file and its directory, from the post date
+    pub mtime: Option<SystemTime>,
+    /// Filter conditions left to check against the size and type from the HEAD request
+    pub filter: Filter,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            segments: 1,
+            segment_threshold: 64 * 1024 * 1024,
+            verify: true,
+            store: None,
+            mtime: None,
+            filter: Filter::default(),
+        }
+    }
+}
 
 pub struct DownloadInfo {
-    pub web_name: String,
-    pub user_id: String,
-    pub post_id: Option<String>,
+    pub service: Service,
+    pub user_id: CreatorId,
+    pub post_id: Option<PostId>,
 }
 
-/// 提取 web_name 和 user_id
+/// 提取 service 和 user_id
 pub fn extract_info(url: &str) -> Result<DownloadInfo> {
     let url = Url::parse(url)?;
     let mut segments = url
         .path_segments()
         .ok_or_else(|| anyhow!("error: please provide an url with base"))?;
-    let web_name = segments
+    let service = segments
         .next()
-        .ok_or_else(|| anyhow!("web_name not found in url"))?
+        .ok_or_else(|| anyhow!("service not found in url"))?
         .into();
     if segments.next() != Some("user") {
-        anyhow::bail!("wrong url: https://.../<web_name>/user/<user_id>");
+        anyhow::bail!("wrong url: https://.../<service>/user/<user_id>");
     }
     let user_id = segments
         .next()
@@ -50,28 +90,151 @@ pub fn extract_info(url: &str) -> Result<DownloadInfo> {
         Some("post") => segments
             .next()
             .map(|post_id| DownloadInfo {
-                web_name: web_name,
-                user_id: user_id,
+                service,
+                user_id,
                 post_id: Some(post_id.into()),
             })
             .ok_or_else(|| anyhow!("post_id cannot be parsed from URL")),
         None => Ok(DownloadInfo {
-            web_name,
+            service,
             user_id,
             post_id: None,
         }),
         _ => {
-            anyhow::bail!("wrong url: https://.../<web_name>/user/<user_id>/post/<post_id>");
+            anyhow::bail!("wrong url: https://.../<service>/user/<user_id>/post/<post_id>");
         }
     }
 }
 
-pub fn normalize_pathname<'a>(s: &'a str) -> String {
+/// Unicode normalization applied to every path component
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum UnicodeForm {
+    /// Composed, as used by Windows and Linux
+    #[default]
+    Nfc,
+    /// Decomposed, as produced by macOS
+    Nfd,
+    /// Keep names as sent by the server
+    None,
+}
+
+/// Longest path component in bytes
+///
+/// Below the usual limit of 255 to leave room for `.incomplete` and the other working suffixes.
+const MAX_NAME_BYTES: usize = 200;
+/// Longest suffix still treated as an extension when truncating
+const MAX_EXT_BYTES: usize = 16;
+
+/// Device names Windows refuses as file names, with or without extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn replace_specials(s: &str) -> String {
     let specials = "\\/:*?\"<>|\n\r";
-    let result = s
-        .replace(|ch| specials.contains(ch), "_")
-        .replace(|ch: char| ch.is_control(), "_");
-    result.trim_end_matches('.').trim_end().into()
+    s.replace(|ch| specials.contains(ch), "_")
+        .replace(|ch: char| ch.is_control(), "_")
+}
+
+/// Make `s` usable as a single path component on common filesystems
+pub fn normalize_pathname(s: &str, form: UnicodeForm) -> String {
+    let result = replace_specials(s);
+    let result = match form {
+        UnicodeForm::Nfc => result.nfc().collect(),
+        UnicodeForm::Nfd => result.nfd().collect(),
+        UnicodeForm::None => result,
+    };
+    let result = escape_reserved(result.trim_end_matches('.').trim_end());
+    truncate_name(&result)
+}
+
+/// [`normalize_pathname`] as it was before Unicode normalization, reserved name escaping
+/// and truncation, to find names created by older versions
+pub fn legacy_pathname(s: &str) -> String {
+    replace_specials(s).trim_end_matches('.').trim_end().into()
+}
+
+fn split_name(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext))
+            if !stem.is_empty()
+                && ext.len() <= MAX_EXT_BYTES
+                && ext.chars().all(|ch| ch.is_ascii_alphanumeric()) =>
+        {
+            (stem, Some(ext))
+        }
+        _ => (name, None),
+    }
+}
+
+/// `aux.txt` becomes `aux_.txt`
+fn escape_reserved(name: &str) -> String {
+    let (stem, rest) = match name.split_once('.') {
+        Some((stem, _)) => (stem, &name[stem.len()..]),
+        None => (name, ""),
+    };
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.trim_end().eq_ignore_ascii_case(reserved))
+    {
+        format!("{stem}_{rest}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Cut names over [`MAX_NAME_BYTES`] at a char boundary, keeping the extension
+///
+/// A short hash of the full name is appended so names sharing a long prefix stay distinct.
+fn truncate_name(name: &str) -> String {
+    if name.len() <= MAX_NAME_BYTES {
+        return name.to_string();
+    }
+    let hash = format!("{:x}", Sha256::digest(name.as_bytes()));
+    let hash = &hash[..8];
+    let (stem, ext) = split_name(name);
+    let ext = ext.map(|ext| format!(".{ext}")).unwrap_or_default();
+
+    let mut end = MAX_NAME_BYTES - ext.len() - hash.len() - 1;
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}~{hash}{ext}", stem[..end].trim_end())
+}
+
+/// Parse a size like `512K`, `64M` or `1.5G`, with 1024-based units
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| anyhow!("invalid size: {s}"))?;
+    let multiplier: u64 = match unit
+        .trim()
+        .to_ascii_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => anyhow::bail!("invalid size unit: {unit}"),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parse a non-negative number of seconds like `2` or `0.5`
+pub fn parse_seconds(s: &str) -> Result<Duration> {
+    let secs: f64 = s
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("invalid number of seconds: {s}"))?;
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| anyhow!("seconds must be a finite, non-negative number: {s}"))
 }
 
 /// Returns true if passed check
@@ -88,110 +251,387 @@ pub fn whiteblack_regex_filter(white: &RegexSet, black: &RegexSet, heytrack: &st
     }
 }
 
-#[tracing::instrument(skip(api, position))]
+/// Spinner with byte count and rate if `total` is unknown
+fn progress_bar(file_name: &str, total: Option<u64>, position: u16) -> RichProgress {
+    let spinner = Column::Spinner(Spinner::new(
+        &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+        80.0,
+        1.0,
+    ));
+    let columns = match total {
+        Some(_) => vec![
+            spinner,
+            Column::Text(format!("[blue bold]{file_name}")),
+            Column::Animation,
+            Column::Percentage(1),
+            Column::Text("•".to_owned()),
+            Column::CountTotal,
+            Column::Text("•".to_owned()),
+            Column::Rate,
+            Column::Text("•".to_owned()),
+            Column::RemainingTime,
+        ],
+        None => vec![
+            spinner,
+            Column::Text(format!("[blue bold]{file_name}")),
+            Column::Count,
+            Column::Text("•".to_owned()),
+            Column::Rate,
+            Column::Text("•".to_owned()),
+            Column::ElapsedTime,
+        ],
+    };
+    RichProgress::new(
+        tqdm!(
+            total = total.unwrap_or(0) as usize,
+            initial = 0,
+            unit_scale = true,
+            unit_divisor = 1024,
+            unit = "B",
+            desc = file_name,
+            position = position
+        ),
+        columns,
+    )
+}
+
+/// Attempts at getting a file whose content matches the hash in its path
+const VERIFY_ROUNDS: usize = 2;
+
+#[tracing::instrument(skip(api, position, options))]
 pub async fn download_file(
     api: API,
     url: &str,
     save_dir: &Path,
     file_name: &str,
     position: u16,
+    options: &DownloadOptions,
 ) -> Result<()> {
-    if DONE.load(Ordering::Relaxed) {
-        return Ok(());
-    }
     let save_path = save_dir.join(file_name);
+    let hash = verify::expected_hash(url);
+    let expected = hash.clone().filter(|_| options.verify);
+    // files are hashed for the store too, it must only hold content matching its name
+    let hashed = hash.is_some() && (options.verify || options.store.is_some());
 
-    let head_resp = api.head(url).await?;
-    if !head_resp.status().is_success() {
-        anyhow::bail!(
-            "failed to download {} status_code {:?}",
-            url,
-            head_resp.status()
-        );
+    for _ in 0..VERIFY_ROUNDS {
+        if DONE.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let Some((path, actual)) =
+            download_once(&api, url, &save_path, file_name, position, options, hashed).await?
+        else {
+            return Ok(());
+        };
+
+        if let (Some(expected), Some(actual)) = (&expected, &actual) {
+            if expected != actual {
+                verify::quarantine(&path, &save_path, expected, actual).await?;
+                continue;
+            }
+        }
+        if path != save_path {
+            fs::rename(path, &save_path).await?;
+            trace!("Completed downloading {file_name}");
+        }
+        let verified = actual.is_some() && actual == hash;
+        if let (Some(store), Some(hash)) = (&options.store, &hash) {
+            if !verified {
+                warn!("{file_name} does not match its SHA-256, not added to store");
+            } else if let Err(e) = store.insert(hash, &save_path).await {
+                warn!("failed to add {file_name} to store: {e}");
+            }
+        }
+        if let Some(time) = options.mtime {
+            mtime::apply(&save_path, time);
+        }
+        if let Some(hash) = hash.as_ref().filter(|_| verified) {
+            if let Err(e) = verify::mark_verified(&save_path, hash).await {
+                warn!("failed to record {file_name} as verified: {e}");
+            }
+        }
+        if let Some(time) = options.mtime {
+            // writing into the directory just bumped its mtime
+            mtime::apply(save_dir, time);
+        }
+        return Ok(());
     }
+    anyhow::bail!("{file_name} failed SHA-256 verification {VERIFY_ROUNDS} times")
+}
+
+/// Fetch the file unless it already exists, `None` if skipped or interrupted
+///
+/// Returns the path of the finished file, the final or the `.incomplete` one,
+/// and its SHA-256 if `hash` is set.
+async fn download_once(
+    api: &API,
+    url: &str,
+    save_path: &Path,
+    file_name: &str,
+    position: u16,
+    options: &DownloadOptions,
+    hash: bool,
+) -> Result<Option<(PathBuf, Option<String>)>> {
+    let head_resp = match api.head(url).await {
+        Ok(resp) => resp,
+        Err(Error::NotFound { .. }) => {
+            warn!("File not found on server, skipped {file_name}");
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
     let total_size = head_resp
         .headers()
         .get(reqwest::header::CONTENT_LENGTH)
         .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(0);
+        .and_then(|s| s.parse::<u64>().ok());
+
+    let content_type = head_resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let head = Attrs {
+        size: total_size,
+        mime: Some(content_type),
+        ..Default::default()
+    };
+    // nothing is learned after HEAD, what is left depends on a size the server did not send
+    let filter = options.filter.reduce(&head);
+    if !filter.passes() {
+        if filter.may_pass() {
+            info!("Skipped {file_name}, its size is unknown");
+        } else {
+            info!("Skipped {file_name} by filter");
+        }
+        return Ok(None);
+    }
 
     if save_path.exists() && save_path.is_file() {
-        let metadata = std::fs::metadata(&save_path)?;
-        if metadata.len() == total_size && total_size > 0 {
+        let metadata = std::fs::metadata(save_path)?;
+        if total_size.is_some_and(|size| size > 0 && size == metadata.len()) {
+            // the size alone misses corrupt files, only those verified by an earlier run are trusted
+            let actual = match hash {
+                true => match verify::verified_hash(save_path).await {
+                    Some(actual) => Some(actual),
+                    None => {
+                        debug!("Hashing existing {file_name}");
+                        Some(verify::hash_file(save_path).await?)
+                    }
+                },
+                false => None,
+            };
             warn!("File already exists, skipped {}", file_name);
-            return Ok(());
+            return Ok(Some((save_path.to_path_buf(), actual)));
         }
     }
 
     let partial_file_path = save_path.to_string_lossy() + ".incomplete";
     let partial_file_path = PathBuf::from(partial_file_path.as_ref());
 
-    let file = match (partial_file_path.exists(), partial_file_path.is_file()) {
-        (true, false) => {
-            anyhow::bail!("partial_file_path existing as direcotry!");
-        }
-        _ => {
-            File::options()
-                .append(true)
-                .create(true)
-                .open(&partial_file_path)
-                .await?
+    if partial_file_path.is_dir() {
+        anyhow::bail!("partial_file_path existing as direcotry!");
+    }
+
+    let accept_ranges = head_resp
+        .headers()
+        .get(reqwest::header::ACCEPT_RANGES)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"bytes"));
+    // a plain partial file from a single-connection run is resumed as such
+    let segment_size = total_size
+        .filter(|&size| size >= options.segment_threshold.max(1))
+        .filter(|_| options.segments > 1 && accept_ranges)
+        .filter(|_| {
+            segment::state_path(&partial_file_path).exists() || !partial_file_path.exists()
+        });
+    if let Some(total_size) = segment_size {
+        let resumed = segment::resumed_bytes(&partial_file_path, total_size).await;
+        let pb = progress_bar(file_name, Some(total_size - resumed), position);
+        let completed = segment::download(
+            api,
+            url,
+            &partial_file_path,
+            total_size,
+            options.segments,
+            pb,
+        )
+        .await?;
+        if !completed {
+            return Ok(None);
         }
-    };
+        // segments arrive out of order, so the file is hashed once assembled
+        let actual = match hash {
+            true => Some(verify::hash_file(&partial_file_path).await?),
+            false => None,
+        };
+        return Ok(Some((partial_file_path, actual)));
+    }
+
+    let file = File::options()
+        .append(true)
+        .create(true)
+        .open(&partial_file_path)
+        .await?;
 
     let start_pos = file.metadata().await?.len();
-    let mut pb = RichProgress::new(
-        tqdm!(
-            total = (total_size - start_pos) as usize,
-            initial = 0,
-            unit_scale = true,
-            unit_divisor = 1024,
-            unit = "B",
-            desc = file_name,
-            position = position
-        ),
-        vec![
-            Column::Spinner(Spinner::new(
-                &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
-                80.0,
-                1.0,
-            )),
-            Column::Text(format!("[blue bold]{file_name}")),
-            Column::Animation,
-            Column::Percentage(1),
-            Column::Text("•".to_owned()),
-            Column::CountTotal,
-            Column::Text("•".to_owned()),
-            Column::Rate,
-            Column::Text("•".to_owned()),
-            Column::RemainingTime,
-        ],
+    let mut pb = progress_bar(
+        file_name,
+        total_size.map(|size| size.saturating_sub(start_pos)),
+        position,
     );
 
-    let resp = api.get_stream(url, start_pos).await?;
-    if !resp.status().is_success() {
-        anyhow::bail!("failed to download {} status_code {:?}", url, resp.status());
-    }
+    let mut hasher = match hash {
+        true => {
+            let mut hasher = Sha256::new();
+            verify::hash_prefix(&mut hasher, &partial_file_path, start_pos).await?;
+            Some(hasher)
+        }
+        false => None,
+    };
 
+    let retry = api.retry_policy();
     let mut writer = BufWriter::with_capacity(10 * 1024 * 1024, file);
-    let mut stream = resp.bytes_stream();
+    let mut pos = start_pos;
+    let mut attempt = 0;
 
-    while let Some(item) = timeout(Duration::from_secs(10), stream.next()).await? {
-        let data = item?;
+    loop {
+        attempt += 1;
+        let resumed_at = pos;
+        let resp = match api.get_stream(url, pos).await {
+            Ok(resp) => resp,
+            Err(Error::RangeNotSatisfiable { total, .. }) if pos > 0 => {
+                if total == Some(pos) {
+                    // a previous run got everything but stopped before renaming
+                    break;
+                }
+                warn!("partial {file_name} is larger than the remote file, restarting");
+                writer.flush().await?;
+                writer.get_ref().set_len(0).await?;
+                pos = 0;
+                hasher = hasher.map(|_| Sha256::new());
+                pb.reset(total_size.map(|size| size as usize));
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
 
-        if DONE.load(Ordering::Relaxed) {
+        let partial = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        let range_start = ContentRange::from_response(&resp).map(|range| range.start);
+        if !partial && pos > 0 {
+            // the body starts at byte 0, appending it would corrupt the file
+            warn!("server ignored range request, restarting {file_name} from the beginning");
             writer.flush().await?;
-            return Ok(());
+            writer.get_ref().set_len(0).await?;
+            pos = 0;
+            hasher = hasher.map(|_| Sha256::new());
+            pb.reset(total_size.map(|size| size as usize));
         }
+        // where a body of another range belongs is unknown, so it is not written anywhere
+        let mut misplaced = (partial && range_start != Some(pos))
+            .then(|| anyhow!("server sent range starting at {range_start:?} instead of {pos}"));
+        let mut stream = resp.bytes_stream();
+
+        let interrupted = loop {
+            if let Some(e) = misplaced.take() {
+                break Some(e);
+            }
+            let data = match timeout(STREAM_TIMEOUT, stream.next()).await {
+                Ok(Some(Ok(data))) => data,
+                Ok(Some(Err(e))) => break Some(anyhow!(e)),
+                Ok(None) if total_size.is_some_and(|size| pos < size) => {
+                    break Some(anyhow!("connection closed early"))
+                }
+                Ok(None) => break None,
+                Err(_) => break Some(anyhow!("no data received in {STREAM_TIMEOUT:?}")),
+            };
+
+            if DONE.load(Ordering::Relaxed) {
+                writer.flush().await?;
+                return Ok(None);
+            }
+            throttle::acquire(data.len()).await;
+
+            writer.write_all(&data).await?;
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&data);
+            }
+            pos += data.len() as u64;
+            pb.update(data.len())?;
+        };
 
-        writer.write_all(&data).await?;
-        pb.update(data.len())?;
+        let Some(e) = interrupted else {
+            break;
+        };
+        // only give up after repeated failures without any progress
+        if pos > resumed_at {
+            attempt = 1;
+        }
+        if attempt >= retry.max_attempts {
+            writer.flush().await?;
+            return Err(e.context(format!("download interrupted at {pos} bytes")));
+        }
+        writer.flush().await?;
+        let delay = retry.backoff(attempt);
+        warn!("download of {file_name} interrupted at {pos} bytes: {e}, resuming in {delay:?}");
+        if !retry.sleep(delay).await {
+            return Ok(None);
+        }
     }
     writer.flush().await?;
     drop(writer);
-    fs::rename(partial_file_path, save_path).await?;
 
-    trace!("Completed downloading {file_name}");
-    Ok(())
+    Ok(Some((partial_file_path, hasher.map(verify::finish))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_names_are_cut_at_a_char_boundary_keeping_the_extension() {
+        let name = "あ".repeat(100) + ".png";
+        let cut = normalize_pathname(&name, UnicodeForm::Nfc);
+        assert!(cut.len() <= MAX_NAME_BYTES);
+        assert!(cut.starts_with("ああ") && cut.ends_with(".png"));
+
+        let other = normalize_pathname(&("あ".repeat(99) + "い.png"), UnicodeForm::Nfc);
+        assert_ne!(cut, other);
+        assert_eq!(
+            normalize_pathname("short.png", UnicodeForm::Nfc),
+            "short.png"
+        );
+    }
+
+    #[test]
+    fn reserved_names_are_escaped() {
+        let normalize = |name| normalize_pathname(name, UnicodeForm::Nfc);
+        assert_eq!(normalize("aux.txt"), "aux_.txt");
+        assert_eq!(normalize("CON"), "CON_");
+        assert_eq!(normalize("nul.tar.gz"), "nul_.tar.gz");
+        assert_eq!(normalize("Con."), "Con_");
+        assert_eq!(normalize("console.txt"), "console.txt");
+    }
+
+    #[test]
+    fn extensions_are_short_alphanumeric_suffixes() {
+        assert_eq!(split_name("a.tar.gz"), ("a.tar", Some("gz")));
+        assert_eq!(split_name(".hidden"), (".hidden", None));
+        assert_eq!(split_name("v1.0 final"), ("v1.0 final", None));
+        assert_eq!(split_name("no_ext"), ("no_ext", None));
+    }
+
+    #[test]
+    fn names_are_normalized_to_the_requested_form() {
+        let composed = "\u{e9}.png";
+        let decomposed = "e\u{301}.png";
+        assert_eq!(normalize_pathname(decomposed, UnicodeForm::Nfc), composed);
+        assert_eq!(normalize_pathname(composed, UnicodeForm::Nfd), decomposed);
+        assert_eq!(
+            normalize_pathname(decomposed, UnicodeForm::None),
+            decomposed
+        );
+        assert_eq!(
+            normalize_pathname("a/b:c?.png", UnicodeForm::Nfc),
+            "a_b_c_.png"
+        );
+    }
 }
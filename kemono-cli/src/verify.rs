@@ -0,0 +1,194 @@
+//! SHA-256 verification against kemono's content-addressed file paths
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+use tracing::{info, warn};
+
+/// Mismatched files are moved here, relative to the post directory
+const QUARANTINE_DIR: &str = ".quarantine";
+
+/// Hashes of verified files in the post directory, one `size\tmtime\thash\tname` line each
+const VERIFIED_FILE: &str = ".verified";
+
+static FAILURES: Mutex<Vec<Failure>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub path: PathBuf,
+    pub expected: String,
+    pub actual: String,
+    pub quarantined: PathBuf,
+}
+
+/// The hash in a data path or url like `/ab/cd/<sha256>.ext`, lowercase hex
+pub(crate) fn expected_hash(path: &str) -> Option<String> {
+    let path = path.split(['?', '#']).next()?;
+    let file_name = path.rsplit('/').next()?;
+    let stem = file_name.split('.').next()?;
+    (stem.len() == 64 && stem.bytes().all(|b| b.is_ascii_hexdigit()))
+        .then(|| stem.to_ascii_lowercase())
+}
+
+pub(crate) fn finish(hasher: Sha256) -> String {
+    format!("{:x}", hasher.finalize())
+}
+
+/// Feed the first `len` bytes of `path` into `hasher`
+pub(crate) async fn hash_prefix(hasher: &mut Sha256, path: &Path, len: u64) -> Result<()> {
+    let mut reader = File::open(path).await?.take(len);
+    let mut buf = vec![0; 1024 * 1024];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+pub(crate) async fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hash_prefix(&mut hasher, path, u64::MAX).await?;
+    Ok(finish(hasher))
+}
+
+/// Size and mtime in nanoseconds, a recorded hash only holds while both are unchanged
+fn stamp(path: &Path) -> Option<(u64, u128)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), mtime.as_nanos()))
+}
+
+/// The hash recorded by [`mark_verified`], `None` if there is none or the file changed since
+pub(crate) async fn verified_hash(path: &Path) -> Option<String> {
+    let (size, mtime) = stamp(path)?;
+    let name = path.file_name()?.to_str()?;
+    let records = fs::read_to_string(path.parent()?.join(VERIFIED_FILE))
+        .await
+        .ok()?;
+    records.lines().rev().find_map(|line| {
+        let mut fields = line.splitn(4, '\t');
+        let (s, m, hash) = (fields.next()?, fields.next()?, fields.next()?);
+        (fields.next()? == name && s.parse() == Ok(size) && m.parse() == Ok(mtime))
+            .then(|| hash.to_string())
+    })
+}
+
+/// Record that `path` has the SHA-256 `hash`, so later runs need not hash it again
+pub(crate) async fn mark_verified(path: &Path, hash: &str) -> Result<()> {
+    if verified_hash(path).await.as_deref() == Some(hash) {
+        return Ok(());
+    }
+    let (Some((size, mtime)), Some(dir), Some(name)) = (
+        stamp(path),
+        path.parent(),
+        path.file_name().and_then(|name| name.to_str()),
+    ) else {
+        return Ok(());
+    };
+    if name.contains('\n') {
+        return Ok(());
+    }
+    let mut records = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(dir.join(VERIFIED_FILE))
+        .await?;
+    records
+        .write_all(format!("{size}\t{mtime}\t{hash}\t{name}\n").as_bytes())
+        .await?;
+    records.flush().await?;
+    Ok(())
+}
+
+/// Move a file that failed verification out of the way and remember it for [`report`]
+pub(crate) async fn quarantine(
+    path: &Path,
+    save_path: &Path,
+    expected: &str,
+    actual: &str,
+) -> Result<()> {
+    let dir = save_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(QUARANTINE_DIR);
+    fs::create_dir_all(&dir).await?;
+    let file_name = save_path.file_name().unwrap_or(path.as_os_str());
+    // prefixed with the actual hash, so different bad copies of a file do not replace each other
+    let mut quarantined_name = std::ffi::OsString::from(format!("{}_", &actual[..16]));
+    quarantined_name.push(file_name);
+    let quarantined = dir.join(quarantined_name);
+    fs::rename(path, &quarantined).await?;
+
+    warn!(
+        "SHA-256 mismatch for {}: expected {expected}, got {actual}, moved to {}",
+        save_path.display(),
+        quarantined.display()
+    );
+    FAILURES.lock().unwrap().push(Failure {
+        path: save_path.to_path_buf(),
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+        quarantined,
+    });
+    Ok(())
+}
+
+/// All verification failures so far
+pub fn failures() -> Vec<Failure> {
+    FAILURES.lock().unwrap().clone()
+}
+
+/// Log a summary of verification failures, if any
+pub fn report() {
+    let failures = failures();
+    if failures.is_empty() {
+        return;
+    }
+    warn!("{} file(s) failed SHA-256 verification:", failures.len());
+    for Failure {
+        path,
+        expected,
+        actual,
+        quarantined,
+    } in failures
+    {
+        info!(
+            "  {}: expected {expected}, got {actual}, quarantined at {}",
+            path.display(),
+            quarantined.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn recorded_hash_only_holds_while_the_file_is_unchanged() {
+        let dir = std::env::temp_dir().join(format!("kemono-verify-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("a\tb.png");
+        fs::write(&path, b"data").await.unwrap();
+        let hash = hash_file(&path).await.unwrap();
+
+        assert_eq!(verified_hash(&path).await, None);
+        mark_verified(&path, &hash).await.unwrap();
+        assert_eq!(verified_hash(&path).await, Some(hash));
+        fs::write(&path, b"changed").await.unwrap();
+        assert_eq!(verified_hash(&path).await, None);
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}